@@ -0,0 +1,2 @@
+-- Tokens issued by redeeming a link code only act as the character they were linked to
+ALTER TABLE api_tokens ADD COLUMN IF NOT EXISTS character TEXT;
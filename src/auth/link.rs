@@ -0,0 +1,199 @@
+//! Link codes pair the in-game mod with a Discord user's Oblivion character
+use crate::error::OddbotError;
+use async_nats::jetstream::{self, context::CreateKeyValueError, kv};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// How long a link code can be redeemed for
+pub const LINK_CODE_TTL: Duration = Duration::from_secs(10 * 60);
+/// Characters used in link codes, leaving out ones that are easy to mistype
+const LINK_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// How many characters are in a link code
+const LINK_CODE_LENGTH: usize = 8;
+/// How many times we try to find an unused code before giving up
+const MAX_CODE_ATTEMPTS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum LinkError {
+    #[error("Could not create key-value store")]
+    CreateStore(#[from] CreateKeyValueError),
+    #[error("Failed to save link code")]
+    SaveCode(#[from] kv::CreateError),
+    #[error("Failed to get link code")]
+    GetCode(#[from] kv::EntryError),
+    #[error("Failed to redeem link code")]
+    RedeemCode(#[from] kv::PurgeError),
+    #[error("Link code serialization error")]
+    ParseCode(#[from] serde_json::Error),
+    #[error("Could not find an unused link code")]
+    CodesExhausted,
+}
+
+/// A pending link between a Discord user's character and the in-game mod
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LinkCode {
+    pub code: String,
+    pub discord_id: String,
+    pub character: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+impl LinkCode {
+    /// Whether the code can no longer be redeemed
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= OffsetDateTime::now_utc()
+    }
+}
+
+/// A LinkCodeStore hands out one-time link codes and redeems them, backed by a key-value store
+#[derive(Debug)]
+pub struct LinkCodeStore {
+    store: kv::Store,
+}
+
+impl LinkCodeStore {
+    /// Creates a new link code store instance
+    pub async fn new(client: async_nats::Client) -> Result<Self, OddbotError> {
+        let store = jetstream::new(client)
+            .create_key_value(kv::Config {
+                bucket: "oblivion_link_codes".to_string(),
+                max_age: LINK_CODE_TTL,
+                ..Default::default()
+            })
+            .await
+            .map_err(LinkError::CreateStore)?;
+
+        Ok(LinkCodeStore { store })
+    }
+
+    /// Generates a random link code
+    fn generate_code() -> String {
+        let mut rng = rand::thread_rng();
+        (0..LINK_CODE_LENGTH)
+            .map(|_| LINK_CODE_ALPHABET[rng.gen_range(0..LINK_CODE_ALPHABET.len())] as char)
+            .collect()
+    }
+
+    /// Normalizes a code as typed by a player, so case and separators don't matter
+    fn normalize_code(code: &str) -> String {
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+
+    /// Creates a link code for a Discord user's character
+    pub async fn create(
+        &self,
+        discord_id: String,
+        character: String,
+    ) -> Result<LinkCode, LinkError> {
+        for _ in 0..MAX_CODE_ATTEMPTS {
+            let link = LinkCode {
+                code: Self::generate_code(),
+                discord_id: discord_id.clone(),
+                character: character.clone(),
+                expires_at: OffsetDateTime::now_utc() + LINK_CODE_TTL,
+            };
+            let data = serde_json::to_vec(&link)?;
+
+            match self.store.create(&link.code, data.into()).await {
+                Ok(_) => {
+                    tracing::debug!("Created link code for user {}", discord_id);
+                    return Ok(link);
+                }
+                Err(e) if e.kind() == kv::CreateErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(LinkError::CodesExhausted)
+    }
+
+    /// Redeems a link code, which only ever succeeds once
+    pub async fn redeem(&self, code: &str) -> Result<Option<LinkCode>, LinkError> {
+        let code = Self::normalize_code(code);
+        if code.len() != LINK_CODE_LENGTH {
+            return Ok(None);
+        }
+
+        let entry = match self.store.entry(code.as_str()).await? {
+            Some(entry) if entry.operation == kv::Operation::Put => entry,
+            _ => return Ok(None),
+        };
+        let link: LinkCode = serde_json::from_slice(&entry.value)?;
+
+        // Purging against the revision we read means only one redeemer can win
+        if let Err(e) = self
+            .store
+            .purge_expect_revision(code.as_str(), Some(entry.revision))
+            .await
+        {
+            tracing::warn!("Link code was redeemed concurrently: {:?}", e);
+            return Ok(None);
+        }
+
+        if link.is_expired() {
+            return Ok(None);
+        }
+
+        Ok(Some(link))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizing_ignores_case_whitespace_and_dashes() {
+        assert_eq!(LinkCodeStore::normalize_code("abcd-2345"), "ABCD2345");
+        assert_eq!(LinkCodeStore::normalize_code("  AbCd 2345\n"), "ABCD2345");
+        assert_eq!(LinkCodeStore::normalize_code("a-b-c-d-2-3-4-5"), "ABCD2345");
+    }
+
+    #[test]
+    fn generated_codes_use_the_alphabet() {
+        for _ in 0..100 {
+            let code = LinkCodeStore::generate_code();
+            assert_eq!(code.len(), LINK_CODE_LENGTH);
+            assert!(code.bytes().all(|c| LINK_CODE_ALPHABET.contains(&c)));
+            // A generated code survives normalization untouched
+            assert_eq!(LinkCodeStore::normalize_code(&code), code);
+        }
+    }
+
+    #[test]
+    fn codes_expire() {
+        let mut link = LinkCode {
+            code: "ABCD2345".to_string(),
+            discord_id: "1".to_string(),
+            character: "Martin".to_string(),
+            expires_at: OffsetDateTime::now_utc() + LINK_CODE_TTL,
+        };
+        assert!(!link.is_expired());
+
+        link.expires_at = OffsetDateTime::now_utc() - time::Duration::seconds(1);
+        assert!(link.is_expired());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server at NATS_URL"]
+    async fn codes_can_only_be_redeemed_once() {
+        let client = crate::nats::create_nats_client().await.unwrap();
+        let store = LinkCodeStore::new(client).await.unwrap();
+        let link = store
+            .create("1".to_string(), "Martin".to_string())
+            .await
+            .unwrap();
+
+        let typed = link.code.to_ascii_lowercase();
+        let redeemed = store.redeem(&typed).await.unwrap().unwrap();
+        assert_eq!(redeemed.character, "Martin");
+        assert!(store.redeem(&link.code).await.unwrap().is_none());
+    }
+}
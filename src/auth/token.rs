@@ -0,0 +1,206 @@
+//! API tokens let oblivion-server clients act on behalf of a Discord user
+use crate::error::OddbotError;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, types::time::OffsetDateTime};
+use std::sync::Arc;
+
+/// Prefix on every plaintext token, so they're easy to spot in logs and secret scanners
+const TOKEN_PREFIX: &str = "odd_";
+/// How many random bytes go into a token
+const TOKEN_BYTES: usize = 32;
+
+/// A stored API token, which never holds the plaintext secret
+#[derive(sqlx::FromRow, Clone, Debug)]
+pub struct ApiToken {
+    pub id: String,
+    pub discord_id: String,
+    pub label: String,
+    /// The character this token is bound to, for tokens issued through a link code
+    pub character: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+/// An ApiTokenStore issues, checks and revokes API tokens kept hashed in Postgres
+#[derive(Debug)]
+pub struct ApiTokenStore {
+    db_pool: Arc<PgPool>,
+}
+
+impl ApiTokenStore {
+    /// Creates a new token store instance
+    pub fn new(db_pool: Arc<PgPool>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Generates a new plaintext token
+    fn generate() -> String {
+        let mut secret = [0u8; TOKEN_BYTES];
+        rand::thread_rng().fill_bytes(&mut secret);
+        format!("{}{}", TOKEN_PREFIX, hex::encode(secret))
+    }
+
+    /// Hashes a plaintext token for storage and lookup
+    fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Issues a new token for a Discord user, returning the plaintext secret exactly once
+    pub async fn issue(
+        &self,
+        discord_id: String,
+        label: String,
+    ) -> Result<(ApiToken, String), OddbotError> {
+        self.insert(discord_id, label, None).await
+    }
+
+    /// Issues a new token that can only act as the given character
+    pub async fn issue_for_character(
+        &self,
+        discord_id: String,
+        label: String,
+        character: String,
+    ) -> Result<(ApiToken, String), OddbotError> {
+        self.insert(discord_id, label, Some(character)).await
+    }
+
+    /// Stores the hash of a freshly generated token
+    async fn insert(
+        &self,
+        discord_id: String,
+        label: String,
+        character: Option<String>,
+    ) -> Result<(ApiToken, String), OddbotError> {
+        let plaintext = Self::generate();
+
+        tracing::debug!("Issuing API token '{}' for user {}", label, discord_id);
+        let token = sqlx::query_as::<_, ApiToken>(
+            "INSERT INTO api_tokens (id, discord_id, label, character, token_hash, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING id, discord_id, label, character, created_at, last_used_at, revoked_at",
+        )
+        .bind(ulid::Ulid::new().to_string())
+        .bind(discord_id)
+        .bind(label)
+        .bind(character)
+        .bind(Self::hash(&plaintext))
+        .bind(OffsetDateTime::now_utc())
+        .fetch_one(self.db_pool.as_ref())
+        .await?;
+
+        Ok((token, plaintext))
+    }
+
+    /// Looks up an active token by its plaintext secret, marking it as used
+    pub async fn authenticate(&self, plaintext: &str) -> Result<Option<ApiToken>, OddbotError> {
+        if !plaintext.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+
+        let token = sqlx::query_as::<_, ApiToken>(
+            "UPDATE api_tokens
+             SET last_used_at = $2
+             WHERE token_hash = $1 AND revoked_at IS NULL
+             RETURNING id, discord_id, label, character, created_at, last_used_at, revoked_at",
+        )
+        .bind(Self::hash(plaintext))
+        .bind(OffsetDateTime::now_utc())
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Lists a Discord user's active tokens
+    pub async fn list(&self, discord_id: &str) -> Result<Vec<ApiToken>, OddbotError> {
+        let tokens = sqlx::query_as::<_, ApiToken>(
+            "SELECT id, discord_id, label, character, created_at, last_used_at, revoked_at
+             FROM api_tokens
+             WHERE discord_id = $1 AND revoked_at IS NULL
+             ORDER BY created_at",
+        )
+        .bind(discord_id)
+        .fetch_all(self.db_pool.as_ref())
+        .await?;
+
+        Ok(tokens)
+    }
+
+    /// Revokes one of a Discord user's active tokens, returning whether it was revoked
+    pub async fn revoke(&self, discord_id: &str, token_id: &str) -> Result<bool, OddbotError> {
+        tracing::debug!("Revoking API token {} for user {}", token_id, discord_id);
+        let result = sqlx::query(
+            "UPDATE api_tokens
+             SET revoked_at = $3
+             WHERE id = $1 AND discord_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(token_id)
+        .bind(discord_id)
+        .bind(OffsetDateTime::now_utc())
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every active token belonging to a Discord user, returning how many were revoked
+    pub async fn revoke_all(&self, discord_id: &str) -> Result<u64, OddbotError> {
+        tracing::debug!("Revoking all API tokens for user {}", discord_id);
+        let result = sqlx::query(
+            "UPDATE api_tokens
+             SET revoked_at = $2
+             WHERE discord_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(discord_id)
+        .bind(OffsetDateTime::now_utc())
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_prefixed_hex() {
+        let token = ApiTokenStore::generate();
+        let secret = token.strip_prefix(TOKEN_PREFIX).unwrap();
+
+        assert_eq!(secret.len(), TOKEN_BYTES * 2);
+        assert!(secret.bytes().all(|b| b.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn tokens_are_unique() {
+        assert_ne!(ApiTokenStore::generate(), ApiTokenStore::generate());
+    }
+
+    #[test]
+    fn hashes_are_stable_sha256_hex() {
+        let hash = ApiTokenStore::hash("odd_secret");
+
+        assert_eq!(hash, ApiTokenStore::hash("odd_secret"));
+        assert_ne!(hash, ApiTokenStore::hash("odd_secreT"));
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains("secret"));
+        // SHA-256 of the empty string, so we know it's not some other digest
+        assert_eq!(
+            ApiTokenStore::hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[tokio::test]
+    async fn tokens_without_the_prefix_never_reach_the_database() {
+        // A lazy pool never connects, so this would fail if we queried it
+        let pool = PgPool::connect_lazy("postgres://nobody@localhost:1/nothing").unwrap();
+        let store = ApiTokenStore::new(Arc::new(pool));
+
+        assert!(store.authenticate("not_a_token").await.unwrap().is_none());
+    }
+}
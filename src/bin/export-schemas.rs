@@ -0,0 +1,26 @@
+use clap::Parser;
+use oddbot::{prelude::*, schema::json_schemas};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory to write the JSON Schemas to
+    #[arg(long, default_value = "schemas")]
+    out_dir: PathBuf,
+}
+
+fn main() -> Result<(), OddbotError> {
+    let args = Args::parse();
+
+    std::fs::create_dir_all(&args.out_dir)?;
+    for (name, schema) in json_schemas() {
+        let path = args.out_dir.join(format!("{name}.schema.json"));
+        let mut data = serde_json::to_string_pretty(&schema)?;
+        data.push('\n');
+        std::fs::write(&path, data)?;
+        println!("Wrote {}", path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,371 @@
+//! Simulates many websocket viewers against a running oblivion-server while publishing squeaks
+//!
+//! Every client connects from the same IP, so the server's per-IP and per-token rate limits have to
+//! be lifted first, as `just loadtest-server` does.
+use clap::{Parser, ValueEnum};
+use futures::{SinkExt, StreamExt};
+use oddbot::{
+    feed::protocol::{ClientFrame, Encoding, ServerFrame},
+    nats::create_nats_client,
+    prelude::*,
+    skeever::squeak::Squeak,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::watch, task::JoinSet};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        Error as WsError, Message,
+        client::IntoClientRequest,
+        http::{HeaderValue, StatusCode, header::SEC_WEBSOCKET_PROTOCOL},
+    },
+};
+
+/// Marks squeaks published by a load test, so clients ignore everything else on the feed
+const CONTENT_PREFIX: &str = "loadtest";
+/// Avatar attached to load test squeaks
+const AVATAR_URL: &str = "https://cdn.discordapp.com/embed/avatars/0.png";
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Websocket URL of the oblivion-server to test
+    #[arg(long, default_value = "ws://localhost:3000/ws")]
+    url: String,
+
+    /// How many websocket clients to simulate
+    #[arg(long, default_value_t = 100)]
+    clients: usize,
+
+    /// How many squeaks to publish per second
+    #[arg(long, default_value_t = 10.0)]
+    rate: f64,
+
+    /// How long to publish for, in seconds
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+
+    /// How long to wait for stragglers after publishing stops, in seconds
+    #[arg(long, default_value_t = 5)]
+    drain: u64,
+
+    /// How long to spread client connections over, in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    ramp_up: u64,
+
+    /// API token to connect with, when the server requires one
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Wire encoding the clients ask for
+    #[arg(long, value_enum, default_value_t = WireEncoding::Json)]
+    encoding: WireEncoding,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum WireEncoding {
+    Json,
+    Msgpack,
+    Cbor,
+}
+
+impl From<WireEncoding> for Encoding {
+    fn from(encoding: WireEncoding) -> Self {
+        match encoding {
+            WireEncoding::Json => Encoding::Json,
+            WireEncoding::Msgpack => Encoding::MessagePack,
+            WireEncoding::Cbor => Encoding::Cbor,
+        }
+    }
+}
+
+/// What a single simulated client saw
+#[derive(Default)]
+struct ClientReport {
+    connected: bool,
+    /// Turned away by the server's rate limits
+    rate_limited: bool,
+    disconnected: bool,
+    /// How long each squeak of this run took to first arrive, by sequence number
+    latencies_us: Vec<Option<u64>>,
+    /// Squeaks that arrived again after their first delivery, such as through a replay
+    duplicates: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), OddbotError> {
+    let args = Args::parse();
+    if args.rate <= 0.0 {
+        return Err(OddbotError::InvalidConfig("rate must be positive".into()));
+    }
+
+    let stream_name = OddbotConfig::get_event_stream_name().ok_or(OddbotError::InvalidConfig(
+        "EVENT_STREAM_NAME must be set".into(),
+    ))?;
+    let event_stream = EventStream::connect(stream_name, create_nats_client().await?).await?;
+
+    let run_id = ulid::Ulid::new().to_string();
+    let expected = (args.rate * args.duration as f64).ceil() as usize;
+    let url = Arc::new(client_url(&args));
+    let (stop_sender, stop) = watch::channel(false);
+
+    println!(
+        "Connecting {} clients to {}, run {}",
+        args.clients, args.url, run_id
+    );
+    let mut clients = JoinSet::new();
+    let ramp_delay = Duration::from_millis(args.ramp_up) / args.clients.max(1) as u32;
+    for _ in 0..args.clients {
+        clients.spawn(run_client(
+            url.clone(),
+            args.encoding.into(),
+            run_id.clone(),
+            expected,
+            stop.clone(),
+        ));
+        tokio::time::sleep(ramp_delay).await;
+    }
+
+    println!(
+        "Publishing {} squeaks at {}/s for {}s",
+        expected, args.rate, args.duration
+    );
+    let published = publish_squeaks(&event_stream, &run_id, expected, args.rate).await;
+    let published_count = published.iter().filter(|published| **published).count();
+
+    tokio::time::sleep(Duration::from_secs(args.drain)).await;
+    let _ = stop_sender.send(true);
+
+    let mut reports = Vec::with_capacity(args.clients);
+    while let Some(report) = clients.join_next().await {
+        match report {
+            Ok(report) => reports.push(report),
+            Err(e) => eprintln!("Client task failed: {e}"),
+        }
+    }
+
+    print_report(&reports, &published, published_count);
+    Ok(())
+}
+
+/// Adds the protocol version, topic and token to the server URL
+fn client_url(args: &Args) -> String {
+    let separator = if args.url.contains('?') { '&' } else { '?' };
+    let mut url = format!("{}{}v=1&topics=skeever", args.url, separator);
+    if let Some(token) = &args.token {
+        url.push_str(&format!("&token={token}"));
+    }
+    url
+}
+
+/// Microseconds since the Unix epoch, embedded in squeaks to measure end-to-end latency
+fn now_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// Publishes squeaks at a steady rate, returning which of them made it onto the stream by sequence
+/// number
+async fn publish_squeaks(
+    event_stream: &EventStream,
+    run_id: &str,
+    count: usize,
+    rate: f64,
+) -> Vec<bool> {
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    let mut published = vec![false; count];
+
+    for (sequence, published) in published.iter_mut().enumerate() {
+        interval.tick().await;
+        let squeak = Squeak::builder()
+            .user("loadtest".to_string())
+            .avatar(AVATAR_URL.to_string())
+            .content(format!("{CONTENT_PREFIX} {run_id} {sequence} {}", now_us()))
+            .build();
+        let squeak = match squeak {
+            Ok(squeak) => squeak,
+            Err(e) => {
+                eprintln!("Failed to build squeak: {e}");
+                continue;
+            }
+        };
+
+        match event_stream.publish(EventMessage::from(squeak)).await {
+            Ok(()) => *published = true,
+            Err(e) => eprintln!("Failed to publish squeak {sequence}: {e}"),
+        }
+    }
+
+    published
+}
+
+/// Reads a load test squeak's sequence number and publish time
+fn parse_content(content: &str, run_id: &str) -> Option<(usize, u64)> {
+    let mut parts = content.split(' ');
+    if parts.next()? != CONTENT_PREFIX || parts.next()? != run_id {
+        return None;
+    }
+    let sequence = parts.next()?.parse().ok()?;
+    let published_us = parts.next()?.parse().ok()?;
+    Some((sequence, published_us))
+}
+
+/// Connects a single client and records what it receives until told to stop
+async fn run_client(
+    url: Arc<String>,
+    encoding: Encoding,
+    run_id: String,
+    expected: usize,
+    mut stop: watch::Receiver<bool>,
+) -> ClientReport {
+    let mut report = ClientReport {
+        latencies_us: vec![None; expected],
+        ..Default::default()
+    };
+
+    let mut request = match url.as_str().into_client_request() {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("Invalid websocket URL: {e}");
+            return report;
+        }
+    };
+    request.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(encoding.subprotocol()),
+    );
+
+    let mut socket = match connect_async(request).await {
+        Ok((socket, _)) => socket,
+        Err(WsError::Http(response)) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+            report.rate_limited = true;
+            return report;
+        }
+        Err(e) => {
+            eprintln!("Failed to connect: {e}");
+            return report;
+        }
+    };
+    report.connected = true;
+
+    loop {
+        tokio::select! {
+            _ = stop.changed() => break,
+            message = socket.next() => {
+                let frame = match message {
+                    Some(Ok(Message::Text(text))) => serde_json::from_str::<ServerFrame>(&text)
+                        .map_err(|e| e.to_string()),
+                    Some(Ok(Message::Binary(bytes))) => encoding
+                        .decode::<ServerFrame>(&bytes)
+                        .map_err(|e| e.to_string()),
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => {
+                        report.disconnected = true;
+                        break;
+                    }
+                    Some(Ok(_)) => continue,
+                };
+
+                match frame {
+                    Ok(ServerFrame::Squeak(squeak)) => {
+                        let received_us = now_us();
+                        let Some((sequence, published_us)) = parse_content(&squeak.content, &run_id)
+                        else {
+                            continue;
+                        };
+                        match report.latencies_us.get_mut(sequence) {
+                            Some(Some(_)) => report.duplicates += 1,
+                            Some(latency) => {
+                                *latency = Some(received_us.saturating_sub(published_us));
+                            }
+                            None => {}
+                        }
+                    }
+                    Ok(ServerFrame::Ping { nonce }) => {
+                        let pong = encoding
+                            .encode(&ClientFrame::Pong { nonce })
+                            .unwrap_or_default();
+                        let pong = match encoding.is_binary() {
+                            true => Message::Binary(pong.into()),
+                            false => Message::Text(String::from_utf8(pong).unwrap_or_default().into()),
+                        };
+                        if socket.send(pong).await.is_err() {
+                            report.disconnected = true;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let _ = socket.close(None).await;
+    report
+}
+
+/// Gets a percentile from sorted latencies
+fn percentile(sorted: &[u64], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * percentile).round() as usize;
+    Duration::from_micros(sorted[index])
+}
+
+/// Counts the squeaks a client never received, out of those that were actually published
+fn missed(report: &ClientReport, published: &[bool]) -> usize {
+    published
+        .iter()
+        .zip(&report.latencies_us)
+        .filter(|(published, latency)| **published && latency.is_none())
+        .count()
+}
+
+fn print_report(reports: &[ClientReport], published: &[bool], published_count: usize) {
+    let connected = reports.iter().filter(|report| report.connected).count();
+    let rate_limited = reports.iter().filter(|report| report.rate_limited).count();
+    let disconnected = reports.iter().filter(|report| report.disconnected).count();
+    let missed: usize = reports
+        .iter()
+        .filter(|report| report.connected)
+        .map(|report| missed(report, published))
+        .sum();
+    let duplicates: usize = reports.iter().map(|report| report.duplicates).sum();
+
+    let mut latencies: Vec<u64> = reports
+        .iter()
+        .flat_map(|report| report.latencies_us.iter().flatten().copied())
+        .collect();
+    latencies.sort_unstable();
+
+    println!();
+    println!("Clients connected:    {}/{}", connected, reports.len());
+    println!("Clients rate limited: {}", rate_limited);
+    println!("Clients disconnected: {}", disconnected);
+    println!("Squeaks published:    {}", published_count);
+    println!("Squeaks delivered:    {}", latencies.len());
+    println!("Squeaks duplicated:   {}", duplicates);
+    println!("Squeaks missed:       {}", missed);
+    println!("Latency p50:          {:?}", percentile(&latencies, 0.50));
+    println!("Latency p90:          {:?}", percentile(&latencies, 0.90));
+    println!("Latency p99:          {:?}", percentile(&latencies, 0.99));
+    println!(
+        "Latency max:          {:?}",
+        latencies
+            .last()
+            .map(|max| Duration::from_micros(*max))
+            .unwrap_or_default()
+    );
+    if rate_limited > 0 {
+        println!();
+        println!(
+            "Some clients were rate limited, lift the server's limits with RATE_LIMIT_IP_PER_MINUTE=0 \
+             and RATE_LIMIT_TOKEN_PER_MINUTE=0 or run it with `just loadtest-server`"
+        );
+    }
+}
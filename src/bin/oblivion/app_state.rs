@@ -1,24 +1,137 @@
-use oddbot::{nats::create_nats_client, prelude::*, skeever::squeak::Squeak};
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use crate::{
+    broadcast::BroadcastEvent, cache::RecentEvents, characters::CharacterNames, likes::LikeCounts,
+    media::MediaCache, rate_limit::RateLimiter, revisions::Revisions, stats::FeedStats,
+    websockets::WebSocketConfig,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use oddbot::{
+    auth::{link::LinkCodeStore, token::ApiTokenStore},
+    db,
+    discord::character::CharacterStore,
+    nats::create_nats_client,
+    prelude::*,
+    skeever::like::LikeStore,
+};
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use tokio::sync::{Semaphore, broadcast};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// How many recent events we keep in memory by default
+const DEFAULT_RECENT_EVENTS_CAPACITY: u64 = 1000;
+/// Default burst and per-minute rate for a single client IP
+const DEFAULT_IP_RATE_LIMIT: (u64, u64) = (30, 120);
+/// Default burst and per-minute rate for a single API token
+const DEFAULT_TOKEN_RATE_LIMIT: (u64, u64) = (10, 60);
+/// How old a squeak can be for us to keep its edits, deletions and likes by default
+const DEFAULT_REVISIONS_MAX_AGE: Duration = Duration::from_secs(60 * 60 * 24 * 30);
+/// How many history replays may read from the event stream at once by default
+const DEFAULT_MAX_CONCURRENT_REPLAYS: u64 = 16;
 
 #[derive(Clone, Debug)]
 pub struct AppState {
-    pub event_sender: broadcast::Sender<Squeak>,
+    pub event_sender: broadcast::Sender<Arc<BroadcastEvent>>,
+    pub ws_config: WebSocketConfig,
+    /// One event stream connection shared by every route and websocket
+    pub event_stream: Arc<EventStream>,
+    pub recent_events: Arc<RecentEvents>,
+    pub revisions: Arc<Revisions>,
+    pub like_store: Arc<LikeStore>,
+    pub like_counts: Arc<LikeCounts>,
+    pub feed_stats: Arc<FeedStats>,
+    pub token_store: Arc<ApiTokenStore>,
+    pub character_store: Arc<CharacterStore>,
+    pub character_names: Arc<CharacterNames>,
+    pub link_store: Arc<LinkCodeStore>,
+    pub nats_client: async_nats::Client,
+    /// Cancelled when the server starts shutting down
+    pub shutdown: CancellationToken,
+    /// Long-lived websocket and SSE connections we wait on during shutdown
+    pub connections: TaskTracker,
+    pub ip_rate_limiter: Arc<RateLimiter<IpAddr>>,
+    pub token_rate_limiter: Arc<RateLimiter<String>>,
+    /// Bounds the ephemeral consumers that replays create on the event stream
+    pub replay_permits: Arc<Semaphore>,
+    pub metrics: PrometheusHandle,
+    pub media: Arc<MediaCache>,
+    /// Where the server is reachable from, without a trailing slash, so pages and feeds can link
+    /// back to themselves
+    pub public_url: String,
 }
 
 impl AppState {
-    pub async fn init() -> Result<Self, OddbotError> {
+    pub async fn init(metrics: PrometheusHandle) -> Result<Self, OddbotError> {
         let (event_sender, _) = broadcast::channel(100); // Adjust buffer size as needed
 
-        Ok(Self { event_sender })
-    }
+        let ws_config = WebSocketConfig::from_env();
+        tracing::debug!("Using websocket config {:?}", ws_config);
 
-    pub async fn get_event_stream(&self) -> Result<Arc<EventStream>, OddbotError> {
-        let stream_name = std::env::var("EVENT_STREAM_NAME").map_err(OddbotError::EnvVar)?;
+        let public_url = OddbotConfig::get_public_url()
+            .ok_or(OddbotError::InvalidConfig("PUBLIC_URL must be set".into()))?
+            .trim_end_matches('/')
+            .to_string();
+
+        let stream_name = OddbotConfig::get_event_stream_name().ok_or(
+            OddbotError::InvalidConfig("EVENT_STREAM_NAME must be set".into()),
+        )?;
         let nats_client = create_nats_client().await?;
-        let event_stream = EventStream::connect(stream_name, nats_client).await?;
+        let event_stream = Arc::new(EventStream::connect(stream_name, nats_client.clone()).await?);
+        let character_store = Arc::new(CharacterStore::new(nats_client.clone()).await?);
+        let link_store = Arc::new(LinkCodeStore::new(nats_client.clone()).await?);
+        let like_store = Arc::new(LikeStore::new(nats_client.clone()).await?);
+        let media = Arc::new(MediaCache::new(nats_client.clone(), &public_url).await?);
+
+        let db_pool = Arc::new(db::create_db_pool().await?);
+        db::run_migrations(&db_pool).await?;
+        let token_store = Arc::new(ApiTokenStore::new(db_pool));
+
+        let capacity =
+            OddbotConfig::get_recent_events_capacity().unwrap_or(DEFAULT_RECENT_EVENTS_CAPACITY);
+        let recent_events = Arc::new(RecentEvents::new(capacity as usize));
+
+        let ip_rate_limiter = Arc::new(RateLimiter::new(
+            OddbotConfig::get_rate_limit_ip_burst().unwrap_or(DEFAULT_IP_RATE_LIMIT.0),
+            OddbotConfig::get_rate_limit_ip_per_minute().unwrap_or(DEFAULT_IP_RATE_LIMIT.1),
+        ));
+        let token_rate_limiter = Arc::new(RateLimiter::new(
+            OddbotConfig::get_rate_limit_token_burst().unwrap_or(DEFAULT_TOKEN_RATE_LIMIT.0),
+            OddbotConfig::get_rate_limit_token_per_minute().unwrap_or(DEFAULT_TOKEN_RATE_LIMIT.1),
+        ));
+        // Revisions have to cover at least the window new websocket clients get replayed
+        let revisions_max_age = match OddbotConfig::get_revisions_max_age_secs() {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(DEFAULT_REVISIONS_MAX_AGE),
+        };
+        let revisions_max_age = match (revisions_max_age, ws_config.historical_max_age) {
+            (Some(max_age), Some(window)) => Some(max_age.max(window)),
+            _ => None,
+        };
+
+        let max_replays =
+            OddbotConfig::get_max_concurrent_replays().unwrap_or(DEFAULT_MAX_CONCURRENT_REPLAYS);
 
-        Ok(Arc::new(event_stream))
+        Ok(Self {
+            event_sender,
+            ws_config,
+            event_stream,
+            recent_events,
+            revisions: Arc::new(Revisions::new(revisions_max_age)),
+            like_store,
+            like_counts: Arc::new(LikeCounts::new(revisions_max_age)),
+            feed_stats: Arc::new(FeedStats::default()),
+            token_store,
+            character_store,
+            character_names: Arc::new(CharacterNames::default()),
+            link_store,
+            nats_client,
+            shutdown: CancellationToken::new(),
+            connections: TaskTracker::new(),
+            ip_rate_limiter,
+            token_rate_limiter,
+            replay_permits: Arc::new(Semaphore::new(max_replays.max(1) as usize)),
+            metrics,
+            media,
+            public_url,
+        })
     }
 }
@@ -0,0 +1,130 @@
+use crate::{app_state::AppState, error::OblivionServerError};
+use axum::{
+    extract::{Query, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use oddbot::discord::character::Character;
+use serde::Deserialize;
+
+/// Who is behind an authenticated request
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub token_id: String,
+    pub discord_id: String,
+    /// The name of the user's Oblivion character, if they have registered one
+    pub character: Option<String>,
+}
+
+/// Browsers can't set headers on websocket upgrades, so tokens may also come in the query string
+#[derive(Deserialize)]
+struct TokenParams {
+    token: Option<String>,
+}
+
+/// Pulls a bearer token out of the Authorization header or the `token` query parameter
+fn bearer_token(request: &Request) -> Option<String> {
+    let from_header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    from_header.or_else(|| {
+        Query::<TokenParams>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(params)| params.token)
+    })
+}
+
+/// Resolves a plaintext token to the Discord user and character behind it
+async fn authenticate(
+    state: &AppState,
+    plaintext: &str,
+) -> Result<Option<Identity>, OblivionServerError> {
+    let token = state
+        .token_store
+        .authenticate(plaintext)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to authenticate API token: {:?}", e);
+            OblivionServerError::FailedToAuthenticate
+        })?;
+
+    let Some(token) = token else {
+        return Ok(None);
+    };
+
+    let character = Character::get_by_discord_id(&token.discord_id, &state.character_store)
+        .await
+        .map_err(|e| {
+            tracing::error!(
+                "Failed to look up character for {}: {:?}",
+                token.discord_id,
+                e
+            );
+            OblivionServerError::FailedToAuthenticate
+        })?
+        .map(|character| character.name);
+
+    // Linked tokens stop working once their character is deleted or replaced
+    let still_linked = token.character.as_ref().is_none_or(|linked| {
+        character
+            .as_ref()
+            .is_some_and(|current| current.eq_ignore_ascii_case(linked))
+    });
+    if !still_linked {
+        tracing::debug!(
+            "Token {} is linked to a character the user no longer has",
+            token.id
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(Identity {
+        token_id: token.id,
+        discord_id: token.discord_id,
+        character,
+    }))
+}
+
+/// Rejects requests that don't carry a valid API token
+pub async fn require_token(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, OblivionServerError> {
+    let Some(plaintext) = bearer_token(&request) else {
+        return Err(OblivionServerError::Unauthorized);
+    };
+
+    let Some(identity) = authenticate(&state, &plaintext).await? else {
+        return Err(OblivionServerError::Unauthorized);
+    };
+
+    tracing::debug!(
+        "Authenticated request from user {} with token {}",
+        identity.discord_id,
+        identity.token_id
+    );
+    request.extensions_mut().insert(identity);
+    Ok(next.run(request).await)
+}
+
+/// Attaches an identity when a token is given, still rejecting tokens that aren't valid
+pub async fn optional_token(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, OblivionServerError> {
+    if let Some(plaintext) = bearer_token(&request) {
+        let Some(identity) = authenticate(&state, &plaintext).await? else {
+            return Err(OblivionServerError::Unauthorized);
+        };
+        request.extensions_mut().insert(identity);
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,85 @@
+use crate::websockets::Protocol;
+use axum::extract::ws::Message;
+use oddbot::{
+    error::OddbotError,
+    feed::{
+        FeedEvent,
+        protocol::{Encoding, ServerFrame},
+    },
+};
+use serde::Serialize;
+use std::sync::{Arc, OnceLock};
+
+/// How many wire formats a live event can be encoded in
+const WIRE_FORMATS: usize = 4;
+
+/// A live event shared by every subscriber, encoded at most once per wire format
+#[derive(Debug)]
+pub struct BroadcastEvent {
+    event: FeedEvent,
+    encoded: [OnceLock<Option<Message>>; WIRE_FORMATS],
+}
+
+impl BroadcastEvent {
+    pub fn new(event: FeedEvent) -> Arc<Self> {
+        Arc::new(Self {
+            event,
+            encoded: Default::default(),
+        })
+    }
+
+    pub fn event(&self) -> &FeedEvent {
+        &self.event
+    }
+
+    /// Gets the event in a client's wire format, encoding it for the first client that needs it
+    pub fn encoded(
+        &self,
+        protocol: Protocol,
+        encoding: Encoding,
+    ) -> Result<Option<Message>, OddbotError> {
+        let slot = &self.encoded[wire_format(protocol, encoding)];
+        if let Some(message) = slot.get() {
+            return Ok(message.clone());
+        }
+
+        // Racing receivers may both encode, but only one result is kept
+        let message = encode_event(protocol, encoding, &self.event)?;
+        Ok(slot.get_or_init(|| message).clone())
+    }
+}
+
+/// Index of a wire format in the encoded cache
+fn wire_format(protocol: Protocol, encoding: Encoding) -> usize {
+    match (protocol, encoding) {
+        (Protocol::Legacy, _) => 0,
+        (Protocol::V1, Encoding::Json) => 1,
+        (Protocol::V1, Encoding::MessagePack) => 2,
+        (Protocol::V1, Encoding::Cbor) => 3,
+    }
+}
+
+/// Encodes a feed event in a client's wire format, if that format can carry it
+pub fn encode_event(
+    protocol: Protocol,
+    encoding: Encoding,
+    event: &FeedEvent,
+) -> Result<Option<Message>, OddbotError> {
+    let message = match (protocol, event) {
+        (Protocol::Legacy, FeedEvent::Squeak(squeak)) => to_message(Encoding::Json, squeak)?,
+        (Protocol::Legacy, _) => return Ok(None),
+        (Protocol::V1, event) => to_message(encoding, &ServerFrame::from(event.clone()))?,
+    };
+
+    Ok(Some(message))
+}
+
+/// Serializes a value into a websocket message
+pub fn to_message<T: Serialize>(encoding: Encoding, value: &T) -> Result<Message, OddbotError> {
+    let message = match encoding {
+        Encoding::Json => Message::Text(serde_json::to_string(value)?.into()),
+        encoding => Message::Binary(encoding.encode(value)?.into()),
+    };
+
+    Ok(message)
+}
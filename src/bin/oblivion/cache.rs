@@ -0,0 +1,231 @@
+use oddbot::{event_stream::query::ulid_created_at, feed::FeedEvent, prelude::*};
+use std::{collections::VecDeque, sync::RwLock};
+use time::OffsetDateTime;
+
+/// How many of the newest cached events we check for duplicates on push
+const DEDUPE_WINDOW: usize = 64;
+
+/// A bounded, in-memory ring buffer of the most recent feed events
+///
+/// The forwarder task keeps it warm so new clients can get their backlog without touching NATS.
+#[derive(Debug)]
+pub struct RecentEvents {
+    capacity: usize,
+    inner: RwLock<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    events: VecDeque<FeedEvent>,
+    /// Every event created at or after this time is in the buffer
+    complete_since: OffsetDateTime,
+}
+
+impl RecentEvents {
+    /// Creates an empty buffer, which is complete from the moment it is created
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: RwLock::new(Inner {
+                events: VecDeque::with_capacity(capacity),
+                complete_since: OffsetDateTime::now_utc(),
+            }),
+        }
+    }
+
+    /// Seeds the buffer with stored events, which must cover everything since `since`
+    pub fn warm(&self, stored: Vec<FeedEvent>, since: OffsetDateTime) {
+        let mut inner = self.inner.write().unwrap();
+        let live = std::mem::take(&mut inner.events);
+        inner.complete_since = since;
+
+        for event in stored.into_iter().chain(live) {
+            Self::push_inner(&mut inner, self.capacity, event);
+        }
+    }
+
+    /// Adds a live event, evicting the oldest one when full
+    pub fn push(&self, event: FeedEvent) {
+        let mut inner = self.inner.write().unwrap();
+        Self::push_inner(&mut inner, self.capacity, event);
+    }
+
+    fn push_inner(inner: &mut Inner, capacity: usize, event: FeedEvent) {
+        let id = event.id();
+        if inner
+            .events
+            .iter()
+            .rev()
+            .take(DEDUPE_WINDOW)
+            .any(|cached| cached.id() == id)
+        {
+            return;
+        }
+
+        let evicted = match inner.events.len() == capacity {
+            true => inner.events.pop_front(),
+            false => None,
+        };
+        // We only know we hold everything newer than what we just dropped
+        if let Some(evicted_at) = evicted.and_then(|evicted| ulid_created_at(evicted.id())) {
+            inner.complete_since = inner.complete_since.max(evicted_at);
+        }
+        inner.events.push_back(event);
+    }
+
+    /// Checks whether the buffer holds every event a query asks for
+    pub fn covers(&self, query: &EventQuery) -> bool {
+        let inner = self.inner.read().unwrap();
+        Self::covers_inner(&inner, query)
+    }
+
+    fn covers_inner(inner: &Inner, query: &EventQuery) -> bool {
+        let after_time = match query.after {
+            // Nothing we hold comes after a ULID we can't place in time, leave it to the stream
+            Some(EventCursor::Id(id)) => match ulid_created_at(id) {
+                Some(created) => Some(created),
+                None => return false,
+            },
+            Some(EventCursor::Sequence(_)) => return false,
+            None => None,
+        };
+
+        // Without a lower bound we can't tell whether older events were left out
+        after_time
+            .max(query.since)
+            .is_some_and(|oldest| oldest > inner.complete_since)
+    }
+
+    /// Gets a page of the cached events a query asks for, or `None` if the buffer doesn't cover
+    /// its range
+    ///
+    /// Like a page read from the stream, it only holds events on the query's subjects and at
+    /// most `query.limit` of them.
+    pub fn covering(&self, query: &EventQuery) -> Option<Vec<FeedEvent>> {
+        let inner = self.inner.read().unwrap();
+        if !Self::covers_inner(&inner, query) {
+            return None;
+        }
+        let after = match query.after {
+            Some(EventCursor::Id(id)) => Some(id),
+            _ => None,
+        };
+
+        let events = inner
+            .events
+            .iter()
+            .filter(|event| {
+                let Some(created) = ulid_created_at(event.id()) else {
+                    return false;
+                };
+                after.is_none_or(|after| event.id() > after)
+                    && query.since.is_none_or(|since| created >= since)
+                    && query.until.is_none_or(|until| created < until)
+                    && query.allows_subject(&event.subject())
+            })
+            .take(query.limit)
+            .cloned()
+            .collect();
+
+        Some(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oddbot::{
+        game::event::{GameEvent, GameEventKind},
+        skeever::squeak::Squeak,
+    };
+
+    fn squeak() -> FeedEvent {
+        FeedEvent::Squeak(Box::new(
+            serde_json::from_value(serde_json::json!({
+                "id": ulid::Ulid::new(),
+                "content": "",
+                "author": { "name": "Martin", "avatar_url": "" },
+            }))
+            .unwrap(),
+        ))
+    }
+
+    fn game_event() -> FeedEvent {
+        FeedEvent::GameEvent(
+            GameEvent::builder()
+                .character("Martin".to_string())
+                .kind(GameEventKind::LevelUp { level: 2 })
+                .build()
+                .unwrap(),
+        )
+    }
+
+    /// A cache holding a few squeaks and game events, and a query it covers
+    fn cache_and_query() -> (RecentEvents, EventQuery) {
+        let recent_events = RecentEvents::new(10);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let query = EventQuery {
+            since: Some(OffsetDateTime::now_utc()),
+            ..Default::default()
+        };
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        for _ in 0..3 {
+            recent_events.push(squeak());
+            recent_events.push(game_event());
+        }
+        (recent_events, query)
+    }
+
+    #[test]
+    fn only_serves_the_subjects_asked_for() {
+        let (recent_events, query) = cache_and_query();
+        let query = EventQuery {
+            subjects: vec![Squeak::get_subject_filter()],
+            ..query
+        };
+
+        let events = recent_events.covering(&query).unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(
+            events
+                .iter()
+                .all(|event| matches!(event, FeedEvent::Squeak(_)))
+        );
+    }
+
+    #[test]
+    fn serves_at_most_a_page() {
+        let (recent_events, query) = cache_and_query();
+        let query = EventQuery { limit: 4, ..query };
+
+        assert_eq!(recent_events.covering(&query).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn leaves_ulids_past_year_9999_to_the_stream() {
+        let recent_events = RecentEvents::new(10);
+        let query = EventQuery {
+            after: Some(EventCursor::Id(
+                ulid::Ulid::from_string("7ZZZZZZZZZZZZZZZZZZZZZZZZZ").unwrap(),
+            )),
+            ..Default::default()
+        };
+
+        assert!(!recent_events.covers(&query));
+        assert!(recent_events.covering(&query).is_none());
+    }
+
+    #[test]
+    fn covers_queries_starting_after_it_was_created() {
+        let recent_events = RecentEvents::new(10);
+        let query = EventQuery {
+            since: Some(OffsetDateTime::now_utc() + time::Duration::minutes(1)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            recent_events.covering(&query).map(|events| events.len()),
+            Some(0)
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{app_state::AppState, error::OblivionServerError, extract::Path, telemetry};
+use axum::{Json, extract::State};
+use futures::StreamExt;
+use oddbot::discord::character::{Character, CharacterProfile};
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+
+/// How long to wait before watching characters again after the watch failed
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Which discord id each character name belongs to, kept current from the character store
+#[derive(Debug, Default)]
+pub struct CharacterNames {
+    inner: RwLock<NameIndex>,
+}
+
+#[derive(Debug, Default)]
+struct NameIndex {
+    /// Lowercased character name to discord id
+    ids: HashMap<String, String>,
+    /// Discord id to lowercased character name, so renames and deletes can drop the old name
+    names: HashMap<String, String>,
+}
+
+impl CharacterNames {
+    /// Gets the discord id of the character with a name, ignoring case
+    pub fn id_of(&self, name: &str) -> Option<String> {
+        self.inner
+            .read()
+            .unwrap()
+            .ids
+            .get(&name.to_lowercase())
+            .cloned()
+    }
+
+    fn set(&self, discord_id: &str, name: Option<&str>) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(old) = inner.names.remove(discord_id) {
+            // Another character may have taken the name since
+            if inner.ids.get(&old).is_some_and(|id| id == discord_id) {
+                inner.ids.remove(&old);
+            }
+        }
+        if let Some(name) = name {
+            let name = name.to_lowercase();
+            inner.ids.insert(name.clone(), discord_id.to_string());
+            inner.names.insert(discord_id.to_string(), name);
+        }
+    }
+}
+
+/// Keeps the character names in sync with the character store until the server shuts down
+pub async fn watch_characters(state: AppState) {
+    while !state.shutdown.is_cancelled() {
+        let mut entries = match state.character_store.watch().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::error!("Failed to watch characters: {:?}", e);
+                telemetry::record_nats_error("watch");
+                tokio::select! {
+                    _ = tokio::time::sleep(WATCH_RETRY_DELAY) => {}
+                    _ = state.shutdown.cancelled() => {}
+                }
+                continue;
+            }
+        };
+
+        loop {
+            let entry = tokio::select! {
+                entry = entries.next() => entry,
+                _ = state.shutdown.cancelled() => break,
+            };
+            let entry = match entry {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => {
+                    tracing::error!("Failed to receive characters: {}", e);
+                    telemetry::record_nats_error("watch");
+                    break;
+                }
+                None => break,
+            };
+
+            // Deleted and purged entries have no value
+            let character = serde_json::from_slice::<Character>(&entry.value).ok();
+            state
+                .character_names
+                .set(&entry.key, character.as_ref().map(|c| c.name.as_str()));
+        }
+    }
+
+    tracing::info!("Stopped watching characters");
+}
+
+/// Gets a character by name, ignoring case, without scanning the character store
+pub async fn find_by_name(
+    state: &AppState,
+    name: &str,
+) -> Result<Option<Character>, OblivionServerError> {
+    let Some(discord_id) = state.character_names.id_of(name) else {
+        return Ok(None);
+    };
+
+    let character = state
+        .character_store
+        .get_character(&discord_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get character {}: {:?}", name, e);
+            OblivionServerError::FailedToReadCharacters
+        })?;
+
+    // The index can lag behind a rename
+    Ok(character.filter(|character| character.name.to_lowercase() == name.to_lowercase()))
+}
+
+/// Lists the public profile of every registered character
+#[utoipa::path(
+    get,
+    path = "/characters",
+    tag = "characters",
+    responses((status = 200, description = "Every character, sorted by name", body = Vec<CharacterProfile>))
+)]
+pub async fn list_characters(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CharacterProfile>>, OblivionServerError> {
+    let characters = state.character_store.list_characters().await.map_err(|e| {
+        tracing::error!("Failed to list characters: {:?}", e);
+        OblivionServerError::FailedToReadCharacters
+    })?;
+
+    let mut profiles = Vec::with_capacity(characters.len());
+    for character in characters {
+        profiles.push(
+            state
+                .media
+                .localize_profile(CharacterProfile::from(character))
+                .await,
+        );
+    }
+
+    Ok(Json(profiles))
+}
+
+/// Gets a character's public profile by the discord id the character store keys it by
+#[utoipa::path(
+    get,
+    path = "/characters/{id}",
+    tag = "characters",
+    params(("id" = String, Path, description = "Discord id of the character's player")),
+    responses(
+        (status = 200, description = "The character's public profile", body = CharacterProfile),
+        (status = 404, description = "No character with that id", body = crate::error::ErrorBody),
+    )
+)]
+pub async fn get_character(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CharacterProfile>, OblivionServerError> {
+    let character = state
+        .character_store
+        .get_character(&id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get character {}: {:?}", id, e);
+            OblivionServerError::FailedToReadCharacters
+        })?
+        .ok_or(OblivionServerError::CharacterNotFound(id))?;
+
+    Ok(Json(
+        state
+            .media
+            .localize_profile(CharacterProfile::from(character))
+            .await,
+    ))
+}
+
+/// Gets a character's public profile by name, which is how squeaks refer to their author
+#[utoipa::path(
+    get,
+    path = "/characters/by-name/{name}",
+    tag = "characters",
+    params(("name" = String, Path, description = "Character name, ignoring case")),
+    responses(
+        (status = 200, description = "The character's public profile", body = CharacterProfile),
+        (status = 404, description = "No character with that name", body = crate::error::ErrorBody),
+    )
+)]
+pub async fn get_character_by_name(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<CharacterProfile>, OblivionServerError> {
+    let character = find_by_name(&state, &name)
+        .await?
+        .ok_or(OblivionServerError::CharacterNotFound(name))?;
+
+    Ok(Json(
+        state
+            .media
+            .localize_profile(CharacterProfile::from(character))
+            .await,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_found_ignoring_case() {
+        let names = CharacterNames::default();
+        names.set("1", Some("Lucien Lachance"));
+
+        assert_eq!(names.id_of("lucien lachance").as_deref(), Some("1"));
+        assert_eq!(names.id_of("LUCIEN LACHANCE").as_deref(), Some("1"));
+        assert_eq!(names.id_of("Lucien"), None);
+    }
+
+    #[test]
+    fn renames_and_deletes_drop_the_old_name() {
+        let names = CharacterNames::default();
+        names.set("1", Some("Lucien"));
+        names.set("1", Some("Mannimarco"));
+        assert_eq!(names.id_of("Lucien"), None);
+        assert_eq!(names.id_of("Mannimarco").as_deref(), Some("1"));
+
+        names.set("1", None);
+        assert_eq!(names.id_of("Mannimarco"), None);
+    }
+
+    #[test]
+    fn a_name_taken_by_another_character_survives_the_old_owner_leaving() {
+        let names = CharacterNames::default();
+        names.set("1", Some("Lucien"));
+        names.set("2", Some("Lucien"));
+        names.set("1", None);
+
+        assert_eq!(names.id_of("lucien").as_deref(), Some("2"));
+    }
+}
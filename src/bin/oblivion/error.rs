@@ -1,24 +1,164 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use crate::request_id;
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use oddbot::{error::OddbotError, game::event::GameEventError};
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum OblivionServerError {
-    #[error("Failed to save event")]
-    FailedToSaveEvent,
+    #[error("Invalid event: {0}")]
+    InvalidEvent(GameEventError),
     #[error("Failed to publish event")]
-    FailedToPublishEvent,
+    FailedToPublishEvent(OddbotError),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid body: {0}")]
+    InvalidBody(String),
+    #[error("Failed to read events")]
+    FailedToReadEvents(OddbotError),
+    #[error("Missing or invalid API token")]
+    Unauthorized,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Failed to authenticate")]
+    FailedToAuthenticate,
+    #[error("Invalid or expired link code")]
+    InvalidLinkCode,
+    #[error("Failed to link")]
+    FailedToLink,
+    #[error("Character not found: {0}")]
+    CharacterNotFound(String),
+    #[error("Failed to read characters")]
+    FailedToReadCharacters,
+    #[error("Squeak not found: {0}")]
+    SqueakNotFound(ulid::Ulid),
+    #[error("Media not found")]
+    MediaNotFound,
+    #[error("Failed to read media")]
+    FailedToReadMedia,
+    #[error("Failed to read likes")]
+    FailedToReadLikes,
+    #[error("Too many requests")]
+    RateLimited(std::time::Duration),
+    #[error("Not ready: {0}")]
+    NotReady(String),
 }
 
-impl IntoResponse for OblivionServerError {
-    fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match self {
-            OblivionServerError::FailedToPublishEvent => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to publish event")
+/// The body of every error response
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Machine-readable error code, e.g. `invalid_query`
+    #[schema(example = "invalid_query")]
+    code: &'static str,
+    /// Human-readable description of what went wrong
+    message: String,
+    /// ID of the request, also logged by the server and sent as `X-Request-Id`
+    request_id: Option<String>,
+}
+
+impl OblivionServerError {
+    /// Machine-readable code clients can match on
+    pub fn code(&self) -> &'static str {
+        match self {
+            OblivionServerError::InvalidEvent(_) => "invalid_event",
+            OblivionServerError::FailedToPublishEvent(err) if is_upstream(err) => {
+                "event_stream_unavailable"
             }
-            OblivionServerError::FailedToSaveEvent => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save event")
+            OblivionServerError::FailedToPublishEvent(_) => "publish_failed",
+            OblivionServerError::InvalidQuery(_) => "invalid_query",
+            OblivionServerError::InvalidBody(_) => "invalid_body",
+            OblivionServerError::FailedToReadEvents(err) if is_upstream(err) => {
+                "event_stream_unavailable"
             }
-        };
-        (status, error_message).into_response()
+            OblivionServerError::FailedToReadEvents(_) => "read_failed",
+            OblivionServerError::Unauthorized => "unauthorized",
+            OblivionServerError::Forbidden(_) => "forbidden",
+            OblivionServerError::FailedToAuthenticate => "authentication_failed",
+            OblivionServerError::InvalidLinkCode => "invalid_link_code",
+            OblivionServerError::FailedToLink => "link_failed",
+            OblivionServerError::CharacterNotFound(_) => "character_not_found",
+            OblivionServerError::FailedToReadCharacters => "read_characters_failed",
+            OblivionServerError::SqueakNotFound(_) => "squeak_not_found",
+            OblivionServerError::MediaNotFound => "media_not_found",
+            OblivionServerError::FailedToReadMedia => "read_media_failed",
+            OblivionServerError::FailedToReadLikes => "read_likes_failed",
+            OblivionServerError::RateLimited(_) => "rate_limited",
+            OblivionServerError::NotReady(_) => "not_ready",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            OblivionServerError::InvalidEvent(_)
+            | OblivionServerError::InvalidQuery(_)
+            | OblivionServerError::InvalidBody(_)
+            | OblivionServerError::InvalidLinkCode => StatusCode::BAD_REQUEST,
+            OblivionServerError::Unauthorized => StatusCode::UNAUTHORIZED,
+            OblivionServerError::Forbidden(_) => StatusCode::FORBIDDEN,
+            OblivionServerError::CharacterNotFound(_)
+            | OblivionServerError::SqueakNotFound(_)
+            | OblivionServerError::MediaNotFound => StatusCode::NOT_FOUND,
+            OblivionServerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            OblivionServerError::NotReady(_) => StatusCode::SERVICE_UNAVAILABLE,
+            OblivionServerError::FailedToPublishEvent(err)
+            | OblivionServerError::FailedToReadEvents(err)
+                if is_upstream(err) =>
+            {
+                StatusCode::BAD_GATEWAY
+            }
+            OblivionServerError::FailedToPublishEvent(_)
+            | OblivionServerError::FailedToReadEvents(_)
+            | OblivionServerError::FailedToAuthenticate
+            | OblivionServerError::FailedToLink
+            | OblivionServerError::FailedToReadCharacters
+            | OblivionServerError::FailedToReadMedia
+            | OblivionServerError::FailedToReadLikes => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Whether the error came from NATS rather than from us
+fn is_upstream(err: &OddbotError) -> bool {
+    matches!(
+        err,
+        OddbotError::StreamPublish(_)
+            | OddbotError::NatsBatch(_)
+            | OddbotError::NatsConnect(_)
+            | OddbotError::StreamGet(_)
+            | OddbotError::StreamConsumerCreate(_)
+    )
+}
+
+impl IntoResponse for OblivionServerError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        match status.is_server_error() {
+            true => tracing::error!("Request failed with {}: {:?}", status, self),
+            false => tracing::debug!("Request rejected with {}: {}", status, self),
+        }
+
+        let body = Json(ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            request_id: request_id::current(),
+        });
+
+        if let OblivionServerError::RateLimited(retry_after) = self {
+            // Round up so clients never retry before a token is available
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            return (
+                status,
+                [(header::RETRY_AFTER, seconds.max(1).to_string())],
+                body,
+            )
+                .into_response();
+        }
+
+        (status, body).into_response()
     }
 }
@@ -0,0 +1,221 @@
+use crate::{
+    app_state::AppState,
+    auth::Identity,
+    error::OblivionServerError,
+    extract::{Json, Query},
+};
+use axum::{Extension, extract::State, http::StatusCode};
+use oddbot::{
+    event_stream::query::ulid_created_at,
+    game::event::{GameEvent, GameEventKind},
+    prelude::*,
+    skeever::squeak::Squeak,
+};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use utoipa::{IntoParams, ToSchema};
+
+/// A game event as submitted by the Oblivion mod
+#[derive(Deserialize, ToSchema)]
+pub struct NewGameEvent {
+    /// Must match the token's character when given
+    character: Option<String>,
+    #[serde(flatten)]
+    kind: GameEventKind,
+}
+
+/// Returned once a game event has been accepted onto the event stream
+#[derive(Serialize, ToSchema)]
+pub struct EventAccepted {
+    id: ulid::Ulid,
+}
+
+/// Validates a game event and publishes it to the event stream
+#[utoipa::path(
+    post,
+    path = "/events",
+    tag = "events",
+    request_body = NewGameEvent,
+    security(("api_token" = [])),
+    responses(
+        (status = 202, description = "Event accepted onto the stream", body = EventAccepted),
+        (status = 400, description = "Invalid event or body", body = crate::error::ErrorBody),
+        (status = 401, description = "Missing or invalid API token", body = crate::error::ErrorBody),
+        (status = 403, description = "Token has no character or can't post events for this one", body = crate::error::ErrorBody),
+    )
+)]
+#[axum::debug_handler]
+pub async fn save_event(
+    State(state): State<AppState>,
+    Extension(identity): Extension<Identity>,
+    Json(payload): Json<NewGameEvent>,
+) -> Result<(StatusCode, Json<EventAccepted>), OblivionServerError> {
+    // Events are attributed to the token's character, so they can always be traced back to its owner
+    let Some(character) = identity.character else {
+        return Err(OblivionServerError::Forbidden(
+            "register a character before posting events".to_string(),
+        ));
+    };
+    if payload
+        .character
+        .is_some_and(|requested| !requested.eq_ignore_ascii_case(&character))
+    {
+        return Err(OblivionServerError::Forbidden(format!(
+            "token can only post events for {character}"
+        )));
+    }
+
+    let event = GameEvent::builder()
+        .kind(payload.kind)
+        .character(character)
+        .await
+        .map_err(OblivionServerError::InvalidEvent)?;
+    let id = event.id;
+    tracing::debug!(
+        "User {} submitted game event {} for {}",
+        identity.discord_id,
+        id,
+        event.character
+    );
+
+    tracing::debug!("Publishing game event {} to event stream", id);
+    state
+        .event_stream
+        .publish(EventMessage::from(event))
+        .await
+        .map_err(OblivionServerError::FailedToPublishEvent)?;
+
+    Ok((StatusCode::ACCEPTED, Json(EventAccepted { id })))
+}
+
+/// Most events a single page can hold
+const MAX_PAGE_SIZE: usize = 200;
+
+/// Query parameters for paging through stored events
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsParams {
+    /// Sequence number or ULID to read after
+    after: Option<String>,
+    /// How many events to return, at most 200
+    limit: Option<usize>,
+    /// Comma-separated topics, e.g. `skeever,game`
+    topic: Option<String>,
+    /// RFC 3339 timestamp of the oldest event to return
+    since: Option<String>,
+    /// RFC 3339 timestamp to stop reading at
+    until: Option<String>,
+}
+
+impl EventsParams {
+    /// Converts the raw query parameters into an event stream query
+    fn into_query(self) -> Result<EventQuery, OblivionServerError> {
+        let after = self.after.map(|after| parse_cursor(&after)).transpose()?;
+
+        let limit = self.limit.unwrap_or(EventQuery::default().limit);
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            return Err(OblivionServerError::InvalidQuery(format!(
+                "limit must be between 1 and {MAX_PAGE_SIZE}"
+            )));
+        }
+
+        let subjects = match self.topic {
+            Some(topics) => topics
+                .split(',')
+                .map(|topic| match topic.trim() {
+                    "skeever" => Ok(Squeak::get_subject_filter()),
+                    "game" => Ok(GameEvent::get_subject_filter()),
+                    other => Err(OblivionServerError::InvalidQuery(format!(
+                        "unknown topic: {other}"
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(EventQuery {
+            after,
+            since: self.since.as_deref().map(parse_timestamp).transpose()?,
+            until: self.until.as_deref().map(parse_timestamp).transpose()?,
+            subjects,
+            limit,
+        })
+    }
+}
+
+/// Parses an event cursor from a query parameter, rejecting ULIDs we can't place in time
+pub fn parse_cursor(value: &str) -> Result<EventCursor, OblivionServerError> {
+    let invalid = || OblivionServerError::InvalidQuery(format!("invalid cursor: {value}"));
+    let cursor = value.parse::<EventCursor>().map_err(|_| invalid())?;
+    match cursor {
+        EventCursor::Id(id) if ulid_created_at(id).is_none() => Err(invalid()),
+        cursor => Ok(cursor),
+    }
+}
+
+/// Parses an RFC 3339 timestamp from a query parameter
+pub fn parse_timestamp(value: &str) -> Result<OffsetDateTime, OblivionServerError> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .map_err(|_| OblivionServerError::InvalidQuery(format!("invalid timestamp: {value}")))
+}
+
+/// Reads a page of events from the event stream
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(EventsParams),
+    responses(
+        (status = 200, description = "A page of stored events, oldest first", body = EventPage),
+        (status = 400, description = "Invalid query", body = crate::error::ErrorBody),
+    )
+)]
+pub async fn get_events(
+    State(state): State<AppState>,
+    Query(params): Query<EventsParams>,
+) -> Result<Json<EventPage>, OblivionServerError> {
+    let query = params.into_query()?;
+
+    // Each page creates a stream consumer, so share the replay bound with history replays
+    let permit = state.replay_permits.acquire().await.map_err(|e| {
+        OblivionServerError::FailedToReadEvents(OddbotError::StreamRead(e.to_string()))
+    })?;
+    let page = state
+        .event_stream
+        .query(&query)
+        .await
+        .map_err(OblivionServerError::FailedToReadEvents)?;
+    drop(permit);
+
+    Ok(Json(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sequence_and_ulid_cursors() {
+        assert!(matches!(parse_cursor("42"), Ok(EventCursor::Sequence(42))));
+        assert!(matches!(
+            parse_cursor("01JC0000000000000000000000"),
+            Ok(EventCursor::Id(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_ulids_past_year_9999() {
+        assert!(matches!(
+            parse_cursor("7ZZZZZZZZZZZZZZZZZZZZZZZZZ"),
+            Err(OblivionServerError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_garbage_cursors() {
+        assert!(matches!(
+            parse_cursor("not-a-cursor"),
+            Err(OblivionServerError::InvalidQuery(_))
+        ));
+    }
+}
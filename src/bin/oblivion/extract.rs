@@ -0,0 +1,104 @@
+//! Extractors that reject bad requests with our usual error body rather than axum's plain text
+use crate::error::OblivionServerError;
+use axum::{
+    extract::{
+        FromRequest, FromRequestParts,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    response::{IntoResponse, Response},
+};
+
+/// Like `axum::Json`, which it also stands in for as a response
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(OblivionServerError))]
+pub struct Json<T>(pub T);
+
+impl<T> IntoResponse for Json<T>
+where
+    axum::Json<T>: IntoResponse,
+{
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Like `axum::extract::Query`
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(OblivionServerError))]
+pub struct Query<T>(pub T);
+
+/// Like `axum::extract::Path`
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(OblivionServerError))]
+pub struct Path<T>(pub T);
+
+impl From<JsonRejection> for OblivionServerError {
+    fn from(rejection: JsonRejection) -> Self {
+        OblivionServerError::InvalidBody(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for OblivionServerError {
+    fn from(rejection: QueryRejection) -> Self {
+        OblivionServerError::InvalidQuery(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for OblivionServerError {
+    fn from(rejection: PathRejection) -> Self {
+        OblivionServerError::InvalidQuery(rejection.body_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Params {
+        #[allow(dead_code)]
+        limit: usize,
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_an_invalid_body() {
+        let request = Request::post("/")
+            .header("content-type", "application/json")
+            .body(Body::from("{"))
+            .unwrap();
+
+        let rejection = Json::<Params>::from_request(request, &()).await.err();
+        assert!(matches!(
+            rejection,
+            Some(OblivionServerError::InvalidBody(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_content_type_is_an_invalid_body() {
+        let request = Request::post("/")
+            .body(Body::from(r#"{"limit":1}"#))
+            .unwrap();
+
+        let rejection = Json::<Params>::from_request(request, &()).await.err();
+        assert!(matches!(
+            rejection,
+            Some(OblivionServerError::InvalidBody(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn bad_query_is_an_invalid_query() {
+        let (mut parts, _) = Request::get("/?limit=lots").body(()).unwrap().into_parts();
+
+        let rejection = Query::<Params>::from_request_parts(&mut parts, &())
+            .await
+            .err();
+        assert!(matches!(
+            rejection,
+            Some(OblivionServerError::InvalidQuery(_))
+        ));
+    }
+}
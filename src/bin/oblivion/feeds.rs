@@ -0,0 +1,351 @@
+use crate::{app_state::AppState, characters, error::OblivionServerError, extract::Path, history};
+use axum::{
+    Json,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use oddbot::{feed::FeedEvent, prelude::*, skeever::squeak::Squeak};
+use serde::Serialize;
+use std::time::Duration;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// How many squeaks a feed holds
+const FEED_SIZE: usize = 50;
+/// How far back a feed looks for squeaks, so a quiet character's feed doesn't read the whole stream
+const MAX_FEED_AGE: time::Duration = time::Duration::days(30);
+/// Authority used in tag URIs, which must never change once readers have seen an entry
+const TAG_AUTHORITY: &str = "oddlaws.com,2025";
+/// How many characters of a squeak are used for an entry title
+const TITLE_LENGTH: usize = 80;
+
+/// Which format a feed is rendered in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FeedFormat {
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "atom",
+            FeedFormat::Json => "json",
+        }
+    }
+}
+
+/// Splits a feed file name like `Name.atom` into its name and format
+fn parse_feed_file(file: &str) -> Option<(&str, FeedFormat)> {
+    let (name, extension) = file.rsplit_once('.')?;
+    let format = match extension {
+        "atom" => FeedFormat::Atom,
+        "json" => FeedFormat::Json,
+        _ => return None,
+    };
+    Some((name, format))
+}
+
+/// What a feed is about, along with the squeaks in it
+struct Feed {
+    title: String,
+    tag: String,
+    self_url: String,
+    icon: Option<String>,
+    /// Newest first
+    squeaks: Vec<Squeak>,
+}
+
+/// Atom feed of recent squeaks
+#[utoipa::path(
+    get,
+    path = "/feeds/skeever.atom",
+    tag = "feed",
+    responses((status = 200, description = "Atom feed", content_type = "application/atom+xml", body = String))
+)]
+pub async fn skeever_atom(State(state): State<AppState>) -> Result<Response, OblivionServerError> {
+    skeever_feed(state, FeedFormat::Atom).await
+}
+
+/// JSON Feed of recent squeaks
+#[utoipa::path(
+    get,
+    path = "/feeds/skeever.json",
+    tag = "feed",
+    responses((status = 200, description = "JSON Feed 1.1", content_type = "application/feed+json", body = Object))
+)]
+pub async fn skeever_json(State(state): State<AppState>) -> Result<Response, OblivionServerError> {
+    skeever_feed(state, FeedFormat::Json).await
+}
+
+async fn skeever_feed(
+    state: AppState,
+    format: FeedFormat,
+) -> Result<Response, OblivionServerError> {
+    let feed = Feed {
+        title: "Skeever".to_string(),
+        tag: format!("tag:{TAG_AUTHORITY}:skeever"),
+        self_url: format!("{}/feeds/skeever.{}", state.public_url, format.extension()),
+        icon: None,
+        squeaks: recent_squeaks(&state, |_| true).await?,
+    };
+
+    Ok(render(feed, format))
+}
+
+/// Feed of a single character's recent squeaks, as `/feeds/characters/{name}.atom` or `.json`
+#[utoipa::path(
+    get,
+    path = "/feeds/characters/{file}",
+    tag = "feed",
+    params(("file" = String, Path, description = "Character name followed by `.atom` or `.json`")),
+    responses(
+        (status = 200, description = "Atom feed or JSON Feed, depending on the extension", body = String),
+        (status = 404, description = "No character with that name", body = crate::error::ErrorBody),
+    )
+)]
+pub async fn character_feed(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> Result<Response, OblivionServerError> {
+    let Some((name, format)) = parse_feed_file(&file) else {
+        return Err(OblivionServerError::InvalidQuery(
+            "character feeds end in .atom or .json".into(),
+        ));
+    };
+
+    let character = characters::find_by_name(&state, name)
+        .await?
+        .ok_or_else(|| OblivionServerError::CharacterNotFound(name.to_string()))?;
+
+    let squeaks = recent_squeaks(&state, |squeak| squeak.is_by(&character.name)).await?;
+
+    let feed = Feed {
+        title: format!("{} on Skeever", character.name),
+        tag: format!(
+            "tag:{TAG_AUTHORITY}:skeever/characters/{}",
+            character.name.to_lowercase()
+        ),
+        self_url: format!(
+            "{}/feeds/characters/{}.{}",
+            state.public_url,
+            encode_path_segment(&character.name),
+            format.extension()
+        ),
+        icon: match &character.avatar_url {
+            Some(avatar_url) => Some(state.media.localize(avatar_url).await),
+            None => None,
+        },
+        squeaks,
+    };
+
+    Ok(render(feed, format))
+}
+
+/// Reads the newest squeaks matching a filter from the last `MAX_FEED_AGE`, newest first
+pub async fn recent_squeaks(
+    state: &AppState,
+    filter: impl Fn(&Squeak) -> bool,
+) -> Result<Vec<Squeak>, OblivionServerError> {
+    let query = EventQuery {
+        since: OffsetDateTime::now_utc().checked_sub(MAX_FEED_AGE),
+        subjects: vec![Squeak::get_subject()],
+        limit: state.ws_config.historical_batch_size,
+        ..Default::default()
+    };
+    let wanted = |event: &FeedEvent| match event {
+        FeedEvent::Squeak(squeak) => filter(squeak),
+        _ => false,
+    };
+    let newest = history::read_newest(state, query, FEED_SIZE, Duration::ZERO, wanted)
+        .await
+        .map_err(OblivionServerError::FailedToReadEvents)?;
+
+    Ok(newest
+        .into_iter()
+        .rev()
+        .filter_map(|event| match event {
+            FeedEvent::Squeak(squeak) => Some(*squeak),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Percent-encodes a character name for use in a URL path
+pub fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// When a squeak was posted
+pub fn published(squeak: &Squeak) -> String {
+    squeak.posted_at().format(&Rfc3339).unwrap_or_default()
+}
+
+/// When a squeak was last changed
+fn modified(squeak: &Squeak) -> String {
+    squeak
+        .edited_at
+        .unwrap_or_else(|| squeak.posted_at())
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+/// A short title for an entry, since squeaks don't have one
+fn title(squeak: &Squeak) -> String {
+    if squeak.content.is_empty() && squeak.resqueak_of.is_some() {
+        return format!("{} resqueaked a squeak", squeak.author.name);
+    }
+    let mut title: String = squeak.content.chars().take(TITLE_LENGTH).collect();
+    if squeak.content.chars().count() > TITLE_LENGTH {
+        title.push('…');
+    }
+    title
+}
+
+fn render(feed: Feed, format: FeedFormat) -> Response {
+    match format {
+        FeedFormat::Atom => (
+            [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            render_atom(&feed),
+        )
+            .into_response(),
+        FeedFormat::Json => (
+            [(header::CONTENT_TYPE, "application/feed+json; charset=utf-8")],
+            Json(JsonFeed::from(feed)),
+        )
+            .into_response(),
+    }
+}
+
+/// Escapes text for use in XML content and attributes
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_atom(feed: &Feed) -> String {
+    let updated = feed.squeaks.first().map(published).unwrap_or_else(|| {
+        OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default()
+    });
+
+    let mut xml = String::new();
+    xml.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    xml.push('\n');
+    xml.push_str(
+        r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/">"#,
+    );
+    xml.push_str(&format!("<id>{}</id>", escape_xml(&feed.tag)));
+    xml.push_str(&format!("<title>{}</title>", escape_xml(&feed.title)));
+    xml.push_str(&format!("<updated>{updated}</updated>"));
+    xml.push_str(&format!(
+        r#"<link rel="self" type="application/atom+xml" href="{}"/>"#,
+        escape_xml(&feed.self_url)
+    ));
+    if let Some(icon) = &feed.icon {
+        xml.push_str(&format!("<icon>{}</icon>", escape_xml(icon)));
+    }
+
+    for squeak in &feed.squeaks {
+        let published = published(squeak);
+        xml.push_str("<entry>");
+        xml.push_str(&format!(
+            "<id>tag:{TAG_AUTHORITY}:skeever/squeaks/{}</id>",
+            squeak.id
+        ));
+        xml.push_str(&format!("<title>{}</title>", escape_xml(&title(squeak))));
+        xml.push_str(&format!("<published>{published}</published>"));
+        xml.push_str(&format!("<updated>{}</updated>", modified(squeak)));
+        xml.push_str(&format!(
+            "<author><name>{}</name></author>",
+            escape_xml(&squeak.author.name)
+        ));
+        xml.push_str(&format!(
+            r#"<media:thumbnail url="{}"/>"#,
+            escape_xml(&squeak.author.avatar_url)
+        ));
+        xml.push_str(&format!(
+            r#"<content type="text">{}</content>"#,
+            escape_xml(&squeak.content)
+        ));
+        xml.push_str("</entry>");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// A feed in the JSON Feed 1.1 format
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    title: String,
+    content_text: String,
+    date_published: String,
+    date_modified: String,
+    authors: Vec<JsonFeedAuthor>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+    avatar: String,
+}
+
+impl From<Feed> for JsonFeed {
+    fn from(feed: Feed) -> Self {
+        let items = feed
+            .squeaks
+            .into_iter()
+            .map(|squeak| JsonFeedItem {
+                id: squeak.id.to_string(),
+                title: title(&squeak),
+                date_published: published(&squeak),
+                date_modified: modified(&squeak),
+                content_text: squeak.content,
+                authors: vec![JsonFeedAuthor {
+                    name: squeak.author.name,
+                    avatar: squeak.author.avatar_url,
+                }],
+            })
+            .collect();
+
+        Self {
+            version: "https://jsonfeed.org/version/1.1",
+            title: feed.title,
+            feed_url: feed.self_url,
+            icon: feed.icon,
+            items,
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    app_state::AppState, cache::RecentEvents, likes::LikeCounts, media::MediaCache,
+    revisions::Revisions,
+};
+use oddbot::{feed::FeedEvent, prelude::*};
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+/// How many times the wanted number of events the first window back from the end of the stream
+/// spans, and how much each following window grows by
+const TAIL_MARGIN_FACTOR: u64 = 4;
+/// Most events we read back from the end of the stream looking for the newest wanted ones, so rare
+/// filters give up rather than scanning the whole stream
+const MAX_TAIL_SCAN: u64 = 20_000;
+
+/// Reads stored feed events page by page, oldest first
+///
+/// While the recent event cache covers the query, pages are served from memory.
+pub struct HistoryReader<'a> {
+    listener: &'a EventStream,
+    query: EventQuery,
+    recent_events: Option<&'a RecentEvents>,
+    permits: Option<&'a Semaphore>,
+    media: Option<&'a MediaCache>,
+    revisions: Option<&'a Revisions>,
+    likes: Option<&'a LikeCounts>,
+    /// Squeaks read so far, whose edits, deletions and likes were already applied
+    revised: HashSet<ulid::Ulid>,
+    batch_delay: Duration,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> HistoryReader<'a> {
+    /// Creates a new reader for a query, `query.limit` is used as the page size
+    pub fn new(listener: &'a EventStream, query: EventQuery) -> Self {
+        Self {
+            listener,
+            query,
+            recent_events: None,
+            permits: None,
+            media: None,
+            revisions: None,
+            likes: None,
+            revised: HashSet::new(),
+            batch_delay: Duration::from_millis(50),
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Serves pages from the recent event cache while it covers the rest of the range
+    pub fn with_recent_events(mut self, recent_events: &'a RecentEvents) -> Self {
+        self.recent_events = Some(recent_events);
+        self
+    }
+
+    /// Holds a permit while each page is read, bounding how many stream consumers we create at once
+    pub fn with_replay_permits(mut self, permits: &'a Semaphore) -> Self {
+        self.permits = Some(permits);
+        self
+    }
+
+    /// Rewrites media in every event to point at our cache
+    pub fn with_media(mut self, media: &'a MediaCache) -> Self {
+        self.media = Some(media);
+        self
+    }
+
+    /// Shows squeaks as they are now, leaving out deleted ones and edits already applied
+    pub fn with_revisions(mut self, revisions: &'a Revisions) -> Self {
+        self.revisions = Some(revisions);
+        self
+    }
+
+    /// Fills in current like counts, leaving out likes already counted
+    pub fn with_likes(mut self, likes: &'a LikeCounts) -> Self {
+        self.likes = Some(likes);
+        self
+    }
+
+    /// Sets the delay between pages
+    pub fn with_batch_delay(mut self, batch_delay: Duration) -> Self {
+        self.batch_delay = batch_delay;
+        self
+    }
+
+    /// Reads the next page of feed events, returning `None` once history is exhausted
+    pub async fn next_page(&mut self) -> Result<Option<Vec<FeedEvent>>, OddbotError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let cached = self
+            .recent_events
+            .and_then(|recent_events| recent_events.covering(&self.query));
+        let mut events = match cached {
+            Some(cached) => {
+                tracing::debug!(
+                    "Serving {} events from the recent event cache",
+                    cached.len()
+                );
+                match cached.last() {
+                    Some(last) if cached.len() >= self.query.limit => {
+                        self.query.after = Some(EventCursor::Id(last.id()));
+                    }
+                    _ => self.done = true,
+                }
+                cached
+            }
+            None => self.read_page().await?,
+        };
+        if let Some(media) = self.media {
+            let mut localized = Vec::with_capacity(events.len());
+            for event in events {
+                localized.push(media.localize_event(event).await);
+            }
+            events = localized;
+        }
+
+        Ok(Some(self.revise(events)))
+    }
+
+    /// Reads the next page from the event stream
+    async fn read_page(&mut self) -> Result<Vec<FeedEvent>, OddbotError> {
+        // Add a small delay between batches to prevent overwhelming the client
+        if self.started {
+            tokio::time::sleep(self.batch_delay).await;
+        }
+        self.started = true;
+
+        let permit = match self.permits {
+            Some(permits) => Some(
+                permits
+                    .acquire()
+                    .await
+                    .map_err(|e| OddbotError::StreamRead(e.to_string()))?,
+            ),
+            None => None,
+        };
+        let page = self.listener.query(&self.query).await?;
+        drop(permit);
+        match (page.next, page.events.last()) {
+            (Some(_), Some(last)) => {
+                self.query.after = Some(EventCursor::Sequence(last.sequence));
+            }
+            _ => self.done = true, // No more messages
+        }
+
+        Ok(page
+            .events
+            .into_iter()
+            .filter_map(|stored| match FeedEvent::from_stored(stored) {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::error!("Failed to deserialize stored event: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Applies edits, deletions and likes to squeaks, dropping the ones that were already applied
+    fn revise(&mut self, events: Vec<FeedEvent>) -> Vec<FeedEvent> {
+        let Some(revisions) = self.revisions else {
+            return events;
+        };
+
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                FeedEvent::Squeak(mut squeak) => {
+                    self.revised.insert(squeak.id);
+                    if let Some(likes) = self.likes {
+                        squeak.likes = likes.get(squeak.id);
+                    }
+                    revisions.apply(squeak).map(FeedEvent::Squeak)
+                }
+                FeedEvent::SqueakEdit(edit) if self.revised.contains(&edit.squeak_id) => None,
+                FeedEvent::SqueakDelete(delete) if self.revised.contains(&delete.squeak_id) => None,
+                FeedEvent::SqueakLike(like)
+                    if self.likes.is_some() && self.revised.contains(&like.squeak_id) =>
+                {
+                    None
+                }
+                FeedEvent::SqueakUnlike(unlike)
+                    if self.likes.is_some() && self.revised.contains(&unlike.squeak_id) =>
+                {
+                    None
+                }
+                event => Some(event),
+            })
+            .collect()
+    }
+}
+
+/// Reads the newest `limit` wanted events a query allows, oldest first
+///
+/// Rather than paging through the whole query, this reads windows back from the end of the stream,
+/// each `TAIL_MARGIN_FACTOR` times longer than the last, until one holds enough events or reaches
+/// the first event the query allows. Windows never reach back more than `MAX_TAIL_SCAN` events, so
+/// this can return fewer than `limit` events even when older ones exist.
+pub async fn read_newest(
+    state: &AppState,
+    query: EventQuery,
+    limit: usize,
+    batch_delay: Duration,
+    wanted: impl Fn(&FeedEvent) -> bool,
+) -> Result<VecDeque<FeedEvent>, OddbotError> {
+    let Some(floor) = first_sequence(state, &query).await? else {
+        return Ok(VecDeque::new());
+    };
+    let last = state.event_stream.last_sequence().await?;
+    let lowest = last.saturating_sub(MAX_TAIL_SCAN).max(floor);
+    let mut margin = (limit as u64).saturating_mul(TAIL_MARGIN_FACTOR);
+
+    loop {
+        let start = last.saturating_sub(margin).max(lowest);
+        let window = EventQuery {
+            after: Some(EventCursor::Sequence(start.saturating_sub(1))),
+            ..query.clone()
+        };
+        let mut history = HistoryReader::new(&state.event_stream, window)
+            .with_replay_permits(&state.replay_permits)
+            .with_media(&state.media)
+            .with_revisions(&state.revisions)
+            .with_likes(&state.like_counts)
+            .with_batch_delay(batch_delay);
+
+        let mut newest = VecDeque::with_capacity(limit);
+        while let Some(events) = history.next_page().await? {
+            for event in events.into_iter().filter(|event| wanted(event)) {
+                if newest.len() == limit {
+                    newest.pop_front();
+                }
+                newest.push_back(event);
+            }
+        }
+
+        if newest.len() == limit || start == lowest {
+            return Ok(newest);
+        }
+        margin = margin.saturating_mul(TAIL_MARGIN_FACTOR);
+    }
+}
+
+/// Finds the sequence number of the first stored event a query allows
+async fn first_sequence(state: &AppState, query: &EventQuery) -> Result<Option<u64>, OddbotError> {
+    let first = EventQuery {
+        limit: 1,
+        ..query.clone()
+    };
+    let permit = state
+        .replay_permits
+        .acquire()
+        .await
+        .map_err(|e| OddbotError::StreamRead(e.to_string()))?;
+    let page = state.event_stream.query(&first).await?;
+    drop(permit);
+
+    Ok(page.events.first().map(|event| event.sequence))
+}
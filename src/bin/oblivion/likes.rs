@@ -0,0 +1,197 @@
+use crate::{
+    app_state::AppState,
+    error::OblivionServerError,
+    extract::Path,
+    revisions::{horizon, is_tracked},
+    telemetry,
+};
+use axum::{Json, extract::State};
+use futures::StreamExt;
+use oddbot::skeever::like::Likes;
+use serde::Serialize;
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+use utoipa::ToSchema;
+
+/// How long to wait before watching likes again after the watch failed
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How many characters like each recent squeak, kept current from the like store
+///
+/// Like `Revisions`, only squeaks younger than the max age are tracked, older ones are served
+/// without their likes.
+#[derive(Debug, Default)]
+pub struct LikeCounts {
+    inner: RwLock<HashMap<ulid::Ulid, u64>>,
+    /// How old a squeak can be and still have its likes counted, `None` counting every one
+    max_age: Option<Duration>,
+}
+
+impl LikeCounts {
+    /// Creates empty like counts, counting likes of squeaks up to `max_age` old
+    pub fn new(max_age: Option<Duration>) -> Self {
+        Self {
+            inner: RwLock::default(),
+            max_age,
+        }
+    }
+
+    /// Gets how many characters like a squeak
+    pub fn get(&self, squeak_id: ulid::Ulid) -> u64 {
+        self.inner
+            .read()
+            .unwrap()
+            .get(&squeak_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn set(&self, squeak_id: ulid::Ulid, count: u64) {
+        let tracked = is_tracked(squeak_id, horizon(self.max_age));
+        let mut inner = self.inner.write().unwrap();
+        match count {
+            count if count > 0 && tracked => inner.insert(squeak_id, count),
+            _ => inner.remove(&squeak_id),
+        };
+    }
+
+    /// Forgets the likes of squeaks that have aged past the max age
+    pub fn evict_expired(&self) {
+        let horizon = horizon(self.max_age);
+        if horizon.is_none() {
+            return;
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        let before = inner.len();
+        inner.retain(|squeak_id, _| is_tracked(*squeak_id, horizon));
+        tracing::debug!("Evicted likes of {} squeaks", before - inner.len());
+    }
+}
+
+/// Keeps the like counts in sync with the like store until the server shuts down
+pub async fn watch_likes(state: AppState) {
+    while !state.shutdown.is_cancelled() {
+        let mut entries = match state.like_store.watch().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::error!("Failed to watch likes: {:?}", e);
+                telemetry::record_nats_error("watch");
+                tokio::select! {
+                    _ = tokio::time::sleep(WATCH_RETRY_DELAY) => {}
+                    _ = state.shutdown.cancelled() => {}
+                }
+                continue;
+            }
+        };
+
+        loop {
+            let entry = tokio::select! {
+                entry = entries.next() => entry,
+                _ = state.shutdown.cancelled() => break,
+            };
+            let entry = match entry {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => {
+                    tracing::error!("Failed to receive likes: {}", e);
+                    telemetry::record_nats_error("watch");
+                    break;
+                }
+                None => break,
+            };
+
+            let Ok(squeak_id) = ulid::Ulid::from_string(&entry.key) else {
+                tracing::warn!("Ignoring likes for unknown key {}", entry.key);
+                continue;
+            };
+            let count = match serde_json::from_slice::<Likes>(&entry.value) {
+                Ok(likes) => likes.count(),
+                // Deleted and purged entries have no value
+                Err(_) => 0,
+            };
+            state.like_counts.set(squeak_id, count);
+        }
+    }
+
+    tracing::info!("Stopped watching likes");
+}
+
+/// Who likes a squeak
+#[derive(Serialize, ToSchema)]
+pub struct SqueakLikes {
+    pub squeak_id: ulid::Ulid,
+    pub likes: u64,
+    /// Names of the characters who like the squeak, in the order they liked it
+    pub characters: Vec<String>,
+}
+
+/// Gets who likes a squeak
+#[utoipa::path(
+    get,
+    path = "/squeaks/{id}/likes",
+    tag = "feed",
+    params(("id" = String, Path, description = "ULID of the squeak")),
+    responses((status = 200, description = "The squeak's likes", body = SqueakLikes))
+)]
+pub async fn get_likes(
+    State(state): State<AppState>,
+    Path(id): Path<ulid::Ulid>,
+) -> Result<Json<SqueakLikes>, OblivionServerError> {
+    let likes = state.like_store.get(id).await.map_err(|e| {
+        tracing::error!("Failed to get likes of squeak {}: {:?}", id, e);
+        OblivionServerError::FailedToReadLikes
+    })?;
+
+    Ok(Json(SqueakLikes {
+        squeak_id: id,
+        likes: likes.count(),
+        characters: likes.characters,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn tracked(counts: &LikeCounts) -> usize {
+        counts.inner.read().unwrap().len()
+    }
+
+    fn squeak_id(age: Duration) -> ulid::Ulid {
+        ulid::Ulid::from_datetime(SystemTime::now() - age)
+    }
+
+    #[test]
+    fn forgets_squeaks_nobody_likes_anymore() {
+        let counts = LikeCounts::new(None);
+        let id = squeak_id(Duration::ZERO);
+
+        counts.set(id, 2);
+        assert_eq!(counts.get(id), 2);
+        counts.set(id, 0);
+
+        assert_eq!(counts.get(id), 0);
+        assert_eq!(tracked(&counts), 0);
+    }
+
+    #[test]
+    fn ignores_likes_of_squeaks_past_the_max_age() {
+        let counts = LikeCounts::new(Some(Duration::from_secs(60 * 60)));
+
+        counts.set(squeak_id(Duration::from_secs(60)), 1);
+        counts.set(squeak_id(Duration::from_secs(2 * 60 * 60)), 1);
+
+        assert_eq!(tracked(&counts), 1);
+    }
+
+    #[test]
+    fn evicts_likes_once_squeaks_age_out() {
+        let counts = LikeCounts::new(Some(Duration::from_millis(50)));
+
+        counts.set(squeak_id(Duration::ZERO), 1);
+        std::thread::sleep(Duration::from_millis(100));
+        counts.evict_expired();
+
+        assert_eq!(tracked(&counts), 0);
+    }
+}
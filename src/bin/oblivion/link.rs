@@ -0,0 +1,69 @@
+use crate::{app_state::AppState, error::OblivionServerError, extract::Json};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Label given to tokens issued by redeeming a link code
+const LINK_TOKEN_LABEL: &str = "oblivion-mod";
+
+/// A link code as entered into the Oblivion mod
+#[derive(Deserialize, ToSchema)]
+pub struct LinkRequest {
+    code: String,
+}
+
+/// The credential the mod uses for everything it posts afterwards
+#[derive(Serialize, ToSchema)]
+pub struct LinkResponse {
+    token: String,
+    character: String,
+}
+
+/// Redeems a one-time link code for a token bound to the linked character
+#[utoipa::path(
+    post,
+    path = "/link",
+    tag = "auth",
+    request_body = LinkRequest,
+    responses(
+        (status = 200, description = "Token bound to the linked character", body = LinkResponse),
+        (status = 400, description = "Invalid body, or invalid or expired link code", body = crate::error::ErrorBody),
+    )
+)]
+pub async fn redeem_link_code(
+    State(state): State<AppState>,
+    Json(payload): Json<LinkRequest>,
+) -> Result<Json<LinkResponse>, OblivionServerError> {
+    let link = state
+        .link_store
+        .redeem(&payload.code)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to redeem link code: {:?}", e);
+            OblivionServerError::FailedToLink
+        })?
+        .ok_or(OblivionServerError::InvalidLinkCode)?;
+
+    let (token, plaintext) = state
+        .token_store
+        .issue_for_character(
+            link.discord_id,
+            LINK_TOKEN_LABEL.to_string(),
+            link.character,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to issue token for link code: {:?}", e);
+            OblivionServerError::FailedToLink
+        })?;
+
+    tracing::info!(
+        "Linked the Oblivion mod for user {} with token {}",
+        token.discord_id,
+        token.id
+    );
+    Ok(Json(LinkResponse {
+        token: plaintext,
+        character: token.character.unwrap_or_default(),
+    }))
+}
@@ -0,0 +1,475 @@
+use crate::{app_state::AppState, error::OblivionServerError, extract::Path};
+use async_nats::jetstream::{self, kv};
+use axum::{extract::State, http::header, response::IntoResponse};
+use bytes::Bytes;
+use object_store::{
+    ObjectStore, aws::AmazonS3Builder, local::LocalFileSystem, path::Path as ObjectPath,
+};
+use oddbot::{discord::character::CharacterProfile, feed::FeedEvent, prelude::*};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::{sync::RwLock, time::Instant};
+
+/// Where media is kept when no backend is configured
+const DEFAULT_MEDIA_DIR: &str = "media";
+/// Largest file we'll download by default
+const DEFAULT_MAX_MEDIA_BYTES: u64 = 8 * 1024 * 1024;
+/// Hosts we download from by default, so we can't be pointed at internal services
+const DEFAULT_ALLOWED_HOSTS: &[&str] = &["cdn.discordapp.com", "media.discordapp.net"];
+/// Content types we're willing to cache and serve, which leaves out anything a browser could run
+/// scripts from, like SVG
+const ALLOWED_CONTENT_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+/// How long we wait on a download
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many source URLs we remember in memory before starting over
+const MAX_KNOWN_URLS: usize = 10_000;
+/// Most redirects we follow when downloading media
+const MAX_REDIRECTS: usize = 5;
+/// How long we fall back to a source URL that couldn't be cached before trying it again
+const FAILED_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Error, Debug)]
+pub enum MediaError {
+    #[error("Host not allowed: {0}")]
+    HostNotAllowed(String),
+    #[error("Invalid media URL: {0}")]
+    InvalidUrl(String),
+    #[error("Failed to download media")]
+    Download(#[from] reqwest::Error),
+    #[error("Media is larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("Unsupported media type: {0}")]
+    UnsupportedType(String),
+    #[error("Failed to store media")]
+    Store(#[from] object_store::Error),
+    #[error("Failed to read media index")]
+    ReadIndex(#[from] kv::EntryError),
+    #[error("Failed to write media index")]
+    WriteIndex(#[from] kv::PutError),
+    #[error("Media index serialization error")]
+    ParseIndex(#[from] serde_json::Error),
+}
+
+/// What we know about a cached file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct MediaRecord {
+    content_type: String,
+    size: u64,
+}
+
+/// What we remember about a source URL
+#[derive(Clone, Debug)]
+enum KnownUrl {
+    /// Cached under this hash
+    Cached(String),
+    /// Couldn't be cached at this time
+    Failed(Instant),
+}
+
+/// Downloads media referenced by events into a content-addressed store and serves it back
+///
+/// Files live in the object store under their SHA-256 hash. A key-value index maps source URLs
+/// to hashes, so squeaks replayed long after their Discord URLs expired still resolve.
+pub struct MediaCache {
+    objects: Arc<dyn ObjectStore>,
+    index: kv::Store,
+    http: reqwest::Client,
+    base_url: String,
+    allowed_hosts: Vec<String>,
+    max_bytes: u64,
+    known: RwLock<HashMap<String, KnownUrl>>,
+}
+
+impl std::fmt::Debug for MediaCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MediaCache")
+            .field("objects", &self.objects.to_string())
+            .field("base_url", &self.base_url)
+            .field("allowed_hosts", &self.allowed_hosts)
+            .field("max_bytes", &self.max_bytes)
+            .finish()
+    }
+}
+
+impl MediaCache {
+    /// Creates a media cache using the configured backend, serving files under `public_url`
+    pub async fn new(client: async_nats::Client, public_url: &str) -> Result<Self, OddbotError> {
+        let objects: Arc<dyn ObjectStore> = match OddbotConfig::get_media_s3_bucket() {
+            Some(bucket) => Arc::new(
+                AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()
+                    .map_err(|e| OddbotError::InvalidConfig(format!("media S3 bucket: {e}")))?,
+            ),
+            None => {
+                let dir = OddbotConfig::get_media_dir().unwrap_or(DEFAULT_MEDIA_DIR.to_string());
+                std::fs::create_dir_all(&dir)?;
+                Arc::new(
+                    LocalFileSystem::new_with_prefix(&dir)
+                        .map_err(|e| OddbotError::InvalidConfig(format!("media dir: {e}")))?,
+                )
+            }
+        };
+        tracing::info!("Caching media in {}", objects);
+
+        let index = jetstream::new(client)
+            .create_key_value(kv::Config {
+                bucket: "oblivion_media".to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| OddbotError::InvalidConfig(format!("media index: {e}")))?;
+
+        let allowed_hosts = match OddbotConfig::get_media_allowed_hosts() {
+            hosts if hosts.is_empty() => DEFAULT_ALLOWED_HOSTS
+                .iter()
+                .map(|host| host.to_string())
+                .collect(),
+            hosts => hosts,
+        };
+
+        let http = http_client(allowed_hosts.clone())
+            .map_err(|e| OddbotError::InvalidConfig(format!("media HTTP client: {e}")))?;
+
+        Ok(Self {
+            objects,
+            index,
+            http,
+            base_url: public_url.to_string(),
+            allowed_hosts,
+            max_bytes: OddbotConfig::get_media_max_bytes().unwrap_or(DEFAULT_MAX_MEDIA_BYTES),
+            known: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Gets the stable URL we serve a cached file from
+    fn media_url(&self, hash: &str) -> String {
+        format!("{}/media/{}", self.base_url, hash)
+    }
+
+    /// Hashes bytes into the key a file is stored under
+    fn hash(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    /// Rewrites the media in an event to point at our cache
+    pub async fn localize_event(&self, event: FeedEvent) -> FeedEvent {
+        match event {
+            FeedEvent::Squeak(mut squeak) => {
+                squeak.author.avatar_url = self.localize(&squeak.author.avatar_url).await;
+                FeedEvent::Squeak(squeak)
+            }
+            event => event,
+        }
+    }
+
+    /// Rewrites the media in an event without waiting on downloads
+    ///
+    /// Media we haven't cached yet keeps its source URL and gets cached in the background, so live
+    /// events go out straight away and replays pick up the stable URL later.
+    pub async fn localize_event_cached(self: &Arc<Self>, event: FeedEvent) -> FeedEvent {
+        match event {
+            FeedEvent::Squeak(mut squeak) => {
+                match self.lookup(&squeak.author.avatar_url).await {
+                    Some(Some(url)) => squeak.author.avatar_url = url,
+                    Some(None) => {}
+                    None => {
+                        let media = Arc::clone(self);
+                        let url = squeak.author.avatar_url.clone();
+                        tokio::spawn(async move { media.localize(&url).await });
+                    }
+                }
+                FeedEvent::Squeak(squeak)
+            }
+            event => event,
+        }
+    }
+
+    /// Rewrites a character's avatar to point at our cache
+    pub async fn localize_profile(&self, mut profile: CharacterProfile) -> CharacterProfile {
+        if let Some(avatar_url) = &profile.avatar_url {
+            profile.avatar_url = Some(self.localize(avatar_url).await);
+        }
+        profile
+    }
+
+    /// Gets the stable URL for a source URL, caching it first if needed
+    ///
+    /// Falls back to the source URL when it can't be cached, so a broken download never hides
+    /// an event.
+    pub async fn localize(&self, url: &str) -> String {
+        if let Some(known) = self.lookup(url).await {
+            return known.unwrap_or_else(|| url.to_string());
+        }
+
+        let known_url = match self.cache(url).await {
+            Ok(hash) => KnownUrl::Cached(hash),
+            Err(e) => {
+                tracing::warn!("Failed to cache media {}: {:?}", url, e);
+                KnownUrl::Failed(Instant::now())
+            }
+        };
+
+        let mut known = self.known.write().await;
+        if known.len() >= MAX_KNOWN_URLS {
+            known.clear();
+        }
+        known.insert(url.to_string(), known_url.clone());
+
+        match known_url {
+            KnownUrl::Cached(hash) => self.media_url(&hash),
+            KnownUrl::Failed(_) => url.to_string(),
+        }
+    }
+
+    /// Checks what we already know about a source URL without downloading anything
+    ///
+    /// Returns the stable URL for cached media, `Some(None)` for media that recently failed to
+    /// cache, and `None` when it's worth trying to cache.
+    async fn lookup(&self, url: &str) -> Option<Option<String>> {
+        if url.starts_with(&self.media_url("")) {
+            return Some(Some(url.to_string()));
+        }
+
+        match self.known.read().await.get(url)? {
+            KnownUrl::Cached(hash) => Some(Some(self.media_url(hash))),
+            KnownUrl::Failed(at) if at.elapsed() < FAILED_RETRY_AFTER => Some(None),
+            KnownUrl::Failed(_) => None,
+        }
+    }
+
+    /// Looks up a source URL in the index, downloading it if we've never seen it
+    async fn cache(&self, url: &str) -> Result<String, MediaError> {
+        let url_key = format!("url.{}", Self::hash(url.as_bytes()));
+        if let Some(hash) = self.index.get(url_key.as_str()).await? {
+            return Ok(String::from_utf8_lossy(&hash).into_owned());
+        }
+
+        let parsed =
+            reqwest::Url::parse(url).map_err(|_| MediaError::InvalidUrl(url.to_string()))?;
+        if !is_allowed_url(&parsed, &self.allowed_hosts) {
+            return Err(MediaError::HostNotAllowed(
+                parsed.host_str().unwrap_or_default().to_string(),
+            ));
+        }
+
+        let (content_type, data) = self.download(parsed).await?;
+        let hash = Self::hash(&data);
+        let record = MediaRecord {
+            content_type,
+            size: data.len() as u64,
+        };
+
+        // Content addressing makes rewriting the same file harmless
+        self.objects
+            .put(&ObjectPath::from(hash.as_str()), data.into())
+            .await?;
+        self.index
+            .put(format!("blob.{hash}"), serde_json::to_vec(&record)?.into())
+            .await?;
+        self.index.put(url_key, hash.clone().into()).await?;
+        tracing::debug!("Cached {} as {}", url, hash);
+
+        Ok(hash)
+    }
+
+    /// Downloads a file, enforcing the size limit as it streams in
+    async fn download(&self, url: reqwest::Url) -> Result<(String, Bytes), MediaError> {
+        let mut response = self.http.get(url).send().await?.error_for_status()?;
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let content_type = allowed_content_type(content_type)
+            .ok_or_else(|| MediaError::UnsupportedType(content_type.to_string()))?;
+        if response
+            .content_length()
+            .is_some_and(|length| length > self.max_bytes)
+        {
+            return Err(MediaError::TooLarge(self.max_bytes));
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (data.len() + chunk.len()) as u64 > self.max_bytes {
+                return Err(MediaError::TooLarge(self.max_bytes));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok((content_type.to_string(), data.into()))
+    }
+
+    /// Reads a cached file and its content type
+    pub async fn get(&self, hash: &str) -> Result<Option<(String, Bytes)>, MediaError> {
+        let Some(record) = self.index.get(format!("blob.{hash}")).await? else {
+            return Ok(None);
+        };
+        let record: MediaRecord = serde_json::from_slice(&record)?;
+        // Files cached before we narrowed down what we accept aren't served
+        if allowed_content_type(&record.content_type).is_none() {
+            return Ok(None);
+        }
+
+        let data = match self.objects.get(&ObjectPath::from(hash)).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Some((record.content_type, data)))
+    }
+}
+
+/// Whether we download from a URL, which has to be https on one of the allowed hosts
+fn is_allowed_url(url: &reqwest::Url, allowed_hosts: &[String]) -> bool {
+    let host = url.host_str().unwrap_or_default();
+    url.scheme() == "https" && allowed_hosts.iter().any(|allowed| allowed == host)
+}
+
+/// Builds the client media is downloaded with, which only follows redirects to URLs we'd download
+/// from in the first place
+fn http_client(allowed_hosts: Vec<String>) -> reqwest::Result<reqwest::Client> {
+    let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if is_allowed_url(attempt.url(), &allowed_hosts) {
+            attempt.follow()
+        } else {
+            let host = attempt.url().host_str().unwrap_or_default().to_string();
+            attempt.error(MediaError::HostNotAllowed(host))
+        }
+    });
+
+    reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .redirect(redirects)
+        .build()
+}
+
+/// Normalizes a content type, returning it only if it's one we cache
+fn allowed_content_type(content_type: &str) -> Option<&'static str> {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    ALLOWED_CONTENT_TYPES
+        .iter()
+        .find(|allowed| allowed.eq_ignore_ascii_case(essence))
+        .copied()
+}
+
+/// Serves a cached file, which never changes for a given hash
+pub async fn media_handler(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse, OblivionServerError> {
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(OblivionServerError::MediaNotFound);
+    }
+
+    let (content_type, data) = state
+        .media
+        .get(&hash.to_ascii_lowercase())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to read media {}: {:?}", hash, e);
+            OblivionServerError::FailedToReadMedia
+        })?
+        .ok_or(OblivionServerError::MediaNotFound)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CACHE_CONTROL,
+                "public, max-age=31536000, immutable".to_string(),
+            ),
+            // Belt and braces so a file can never be treated as a page
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'".to_string(),
+            ),
+        ],
+        data,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_raster_images() {
+        assert_eq!(allowed_content_type("image/png"), Some("image/png"));
+        assert_eq!(allowed_content_type("IMAGE/JPEG"), Some("image/jpeg"));
+        assert_eq!(
+            allowed_content_type("image/webp; charset=binary"),
+            Some("image/webp")
+        );
+    }
+
+    #[test]
+    fn rejects_anything_scriptable() {
+        assert_eq!(allowed_content_type("image/svg+xml"), None);
+        assert_eq!(allowed_content_type("text/html"), None);
+        assert_eq!(allowed_content_type("image/png-but-not-really"), None);
+        assert_eq!(allowed_content_type(""), None);
+    }
+
+    fn hosts(hosts: &[&str]) -> Vec<String> {
+        hosts.iter().map(|host| host.to_string()).collect()
+    }
+
+    #[test]
+    fn only_allows_https_on_allowed_hosts() {
+        let allowed = hosts(&["cdn.discordapp.com"]);
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+
+        assert!(is_allowed_url(
+            &url("https://cdn.discordapp.com/a.png"),
+            &allowed
+        ));
+        assert!(!is_allowed_url(
+            &url("http://cdn.discordapp.com/a.png"),
+            &allowed
+        ));
+        assert!(!is_allowed_url(
+            &url("https://evil.example/a.png"),
+            &allowed
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_redirects_off_the_allowlist() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Stands in for an allowed host that redirects somewhere it shouldn't
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut requests = 0;
+            while let Ok(Ok((mut socket, _))) =
+                tokio::time::timeout(Duration::from_millis(500), listener.accept()).await
+            {
+                requests += 1;
+                let mut buf = [0; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = "HTTP/1.1 302 Found\r\n\
+                    Location: http://169.254.169.254/latest/meta-data\r\n\
+                    Content-Length: 0\r\n\r\n";
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+            requests
+        });
+
+        let client = http_client(hosts(&["127.0.0.1"])).unwrap();
+        let result = client.get(format!("http://{addr}/avatar.png")).send().await;
+
+        let error = result.unwrap_err();
+        assert!(error.is_redirect());
+        assert_eq!(server.await.unwrap(), 1);
+    }
+}
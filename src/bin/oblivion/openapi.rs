@@ -0,0 +1,72 @@
+use axum::Json;
+use oddbot::schema::PayloadSchemas;
+use std::sync::LazyLock;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        self,
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+};
+
+/// The oblivion-server routes
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "oblivion-server", description = "Skeever and Oblivion game events for the oddlaws community"),
+    paths(
+        crate::health_handler,
+        crate::ready_handler,
+        crate::stats_handler,
+        crate::ws_handler,
+        crate::sse::sse_handler,
+        crate::events::save_event,
+        crate::events::get_events,
+        crate::threads::get_thread,
+        crate::likes::get_likes,
+        crate::link::redeem_link_code,
+        crate::characters::list_characters,
+        crate::characters::get_character,
+        crate::characters::get_character_by_name,
+        crate::feeds::skeever_atom,
+        crate::feeds::skeever_json,
+        crate::feeds::character_feed,
+    ),
+    components(schemas(
+        crate::error::ErrorBody,
+        crate::events::NewGameEvent,
+        crate::events::EventAccepted,
+        crate::link::LinkRequest,
+        crate::link::LinkResponse,
+        crate::stats::FeedStatsSnapshot,
+        crate::threads::Thread,
+        crate::threads::ThreadNode,
+        crate::likes::SqueakLikes,
+    )),
+    modifiers(&ApiTokenSecurity)
+)]
+struct ApiDoc;
+
+/// Describes the bearer tokens handed out by `/token` and `/link`
+struct ApiTokenSecurity;
+
+impl Modify for ApiTokenSecurity {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// The full document, including every payload schema, built once
+static OPENAPI: LazyLock<openapi::OpenApi> = LazyLock::new(|| {
+    let mut doc = ApiDoc::openapi();
+    doc.merge(PayloadSchemas::openapi());
+    doc
+});
+
+/// Serves the OpenAPI document
+pub async fn openapi_handler() -> Json<openapi::OpenApi> {
+    Json(OPENAPI.clone())
+}
@@ -0,0 +1,116 @@
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures::{SinkExt, stream::SplitSink};
+use oddbot::prelude::*;
+use std::time::Duration;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+/// How many messages may be queued up for a single client
+const OUTBOX_SIZE: usize = 256;
+/// How long we wait on a full outbox before deciding the client is stuck
+const STUCK_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a close frame gets to go out before we drop the socket
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A bounded queue of outgoing messages for one websocket client
+///
+/// A dedicated writer task drains the queue, so one slow socket never holds up the broadcast
+/// receiver or the client's incoming frames.
+pub struct Outbox {
+    messages: mpsc::Sender<Message>,
+    close: Option<oneshot::Sender<CloseFrame>>,
+    writer: JoinHandle<()>,
+    stuck: bool,
+}
+
+impl Outbox {
+    /// Spawns the writer task for a socket
+    pub fn new(sink: SplitSink<WebSocket, Message>) -> Self {
+        let (messages, receiver) = mpsc::channel(OUTBOX_SIZE);
+        let (close, close_receiver) = oneshot::channel();
+        let writer = tokio::spawn(write_to_socket(sink, receiver, close_receiver));
+
+        Self {
+            messages,
+            close: Some(close),
+            writer,
+            stuck: false,
+        }
+    }
+
+    /// Queues a message, failing if the client is gone or hasn't drained its queue in time
+    pub async fn send(&mut self, message: Message) -> Result<(), OddbotError> {
+        match tokio::time::timeout(STUCK_CLIENT_TIMEOUT, self.messages.send(message)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(OddbotError::WebsocketSend("client disconnected".into())),
+            Err(_) => {
+                self.stuck = true;
+                Err(OddbotError::WebsocketSend("client outbox is full".into()))
+            }
+        }
+    }
+
+    /// Checks whether the client stopped draining its queue
+    pub fn is_stuck(&self) -> bool {
+        self.stuck
+    }
+
+    /// Closes the connection with a close frame, skipping anything still queued
+    pub async fn close(mut self, frame: CloseFrame) {
+        if let Some(close) = self.close.take() {
+            let _ = close.send(frame);
+        }
+
+        if tokio::time::timeout(CLOSE_TIMEOUT * 2, &mut self.writer)
+            .await
+            .is_err()
+        {
+            self.writer.abort();
+        }
+    }
+}
+
+/// Drains an outbox into the socket until the outbox or socket goes away, or we're told to close
+async fn write_to_socket(
+    mut sink: SplitSink<WebSocket, Message>,
+    mut messages: mpsc::Receiver<Message>,
+    mut close: oneshot::Receiver<CloseFrame>,
+) {
+    loop {
+        let message = tokio::select! {
+            biased;
+            frame = &mut close => {
+                send_close(&mut sink, frame.ok()).await;
+                return;
+            }
+            message = messages.recv() => match message {
+                Some(message) => message,
+                None => return,
+            },
+        };
+
+        tokio::select! {
+            biased;
+            frame = &mut close => {
+                send_close(&mut sink, frame.ok()).await;
+                return;
+            }
+            result = sink.send(message) => {
+                if result.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Best-effort close frame, a stuck socket won't get to see it
+async fn send_close(sink: &mut SplitSink<WebSocket, Message>, frame: Option<CloseFrame>) {
+    let Some(frame) = frame else {
+        return;
+    };
+
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, sink.send(Message::Close(Some(frame)))).await;
+}
@@ -0,0 +1,305 @@
+use crate::{
+    app_state::AppState,
+    characters,
+    error::OblivionServerError,
+    extract::Path,
+    feeds::{encode_path_segment, published, recent_squeaks},
+    history::HistoryReader,
+};
+use axum::extract::State;
+use maud::{DOCTYPE, Markup, html};
+use oddbot::{
+    discord::character::CharacterProfile, event_stream::query::ulid_created_at, feed::FeedEvent,
+    prelude::*, skeever::squeak::Squeak,
+};
+use std::{collections::HashMap, time::Duration};
+
+/// How far either side of a ULID's timestamp we look for the squeak it belongs to
+const PERMALINK_WINDOW: time::Duration = time::Duration::minutes(1);
+/// How much of a squeak goes into link previews
+const PREVIEW_LENGTH: usize = 200;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 40rem; margin: 2rem auto; padding: 0 1rem; background: #1b1a17; color: #e8e2d0; }
+a { color: #d8b36a; }
+header, article { border-bottom: 1px solid #3a372f; padding: 1rem 0; }
+.author { display: flex; align-items: center; gap: 0.75rem; }
+.author img { width: 3rem; height: 3rem; border-radius: 50%; }
+.content { white-space: pre-wrap; }
+time { color: #9a937f; font-size: 0.875rem; }
+.resqueak { color: #9a937f; font-size: 0.875rem; }
+blockquote { border: 1px solid #3a372f; border-radius: 0.5rem; margin: 0.75rem 0; padding: 0 1rem; }
+";
+
+/// Link preview metadata for OpenGraph and Twitter cards
+struct Meta {
+    title: String,
+    description: String,
+    image: Option<String>,
+    url: String,
+}
+
+/// Wraps a page body in the shared layout and meta tags
+fn layout(meta: &Meta, body: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (meta.title) }
+                meta name="description" content=(meta.description);
+                meta property="og:site_name" content="Skeever";
+                meta property="og:type" content="article";
+                meta property="og:title" content=(meta.title);
+                meta property="og:description" content=(meta.description);
+                meta property="og:url" content=(meta.url);
+                @if let Some(image) = &meta.image {
+                    meta property="og:image" content=(image);
+                    meta name="twitter:image" content=(image);
+                }
+                meta name="twitter:card" content="summary";
+                meta name="twitter:title" content=(meta.title);
+                meta name="twitter:description" content=(meta.description);
+                link rel="alternate" type="application/atom+xml" title="Skeever" href="/feeds/skeever.atom";
+                style { (STYLE) }
+            }
+            body {
+                nav { a href="/" { "Skeever" } }
+                (body)
+            }
+        }
+    }
+}
+
+/// Renders a single squeak with its author, and the squeak it resqueaks inline
+fn squeak_article(squeak: &Squeak, originals: &HashMap<ulid::Ulid, Squeak>) -> Markup {
+    let character_url = format!("/c/{}", encode_path_segment(&squeak.author.name));
+    html! {
+        article {
+            div class="author" {
+                img src=(squeak.author.avatar_url) alt="";
+                a href=(character_url) { strong { (squeak.author.name) } }
+                @if squeak.resqueak_of.is_some() && squeak.content.is_empty() {
+                    span class="resqueak" { "resqueaked" }
+                }
+            }
+            @if !squeak.content.is_empty() {
+                p class="content" { (squeak.content) }
+            }
+            @if let Some(original_id) = squeak.resqueak_of {
+                blockquote {
+                    @match originals.get(&original_id) {
+                        Some(original) => (original_article(original)),
+                        None => p { "This squeak is no longer available." },
+                    }
+                }
+            }
+            a href={ "/s/" (squeak.id) } {
+                time datetime=(published(squeak)) { (published(squeak)) }
+            }
+            @if let Some(parent_id) = squeak.parent_id {
+                " "
+                a class="reply" href={ "/s/" (parent_id) } { "in reply to" }
+            }
+            @if squeak.edited_at.is_some() {
+                " "
+                span class="edited" { "(edited)" }
+            }
+            @if squeak.likes > 0 {
+                " "
+                span class="likes" { (squeak.likes) " 👍" }
+            }
+        }
+    }
+}
+
+/// Renders a resqueaked squeak inside the squeak that resqueaks it
+fn original_article(original: &Squeak) -> Markup {
+    let character_url = format!("/c/{}", encode_path_segment(&original.author.name));
+    html! {
+        div class="author" {
+            img src=(original.author.avatar_url) alt="";
+            a href=(character_url) { strong { (original.author.name) } }
+        }
+        p class="content" { (original.content) }
+        a href={ "/s/" (original.id) } {
+            time datetime=(published(original)) { (published(original)) }
+        }
+    }
+}
+
+/// Finds the squeaks resqueaked by the given ones, looking in the given ones first
+async fn resqueaked_originals(
+    state: &AppState,
+    squeaks: &[Squeak],
+) -> Result<HashMap<ulid::Ulid, Squeak>, OblivionServerError> {
+    let mut originals = HashMap::new();
+    for original_id in squeaks.iter().filter_map(|squeak| squeak.resqueak_of) {
+        if originals.contains_key(&original_id) {
+            continue;
+        }
+        let shown = squeaks.iter().find(|squeak| squeak.id == original_id);
+        let original = match shown {
+            Some(original) => Some(original.clone()),
+            None => find_squeak(state, original_id).await?,
+        };
+        if let Some(original) = original {
+            originals.insert(original_id, original);
+        }
+    }
+    Ok(originals)
+}
+
+/// Trims text down for a link preview
+fn preview(text: &str) -> String {
+    let mut preview: String = text.chars().take(PREVIEW_LENGTH).collect();
+    if text.chars().count() > PREVIEW_LENGTH {
+        preview.push('…');
+    }
+    preview
+}
+
+/// The Skeever timeline
+pub async fn timeline(State(state): State<AppState>) -> Result<Markup, OblivionServerError> {
+    let squeaks = recent_squeaks(&state, |_| true).await?;
+    let originals = resqueaked_originals(&state, &squeaks).await?;
+    let meta = Meta {
+        title: "Skeever".to_string(),
+        description: "What's being squeaked about across Cyrodiil".to_string(),
+        image: None,
+        url: format!("{}/", state.public_url),
+    };
+
+    Ok(layout(
+        &meta,
+        html! {
+            header { h1 { "Skeever" } }
+            @if squeaks.is_empty() {
+                p { "Nobody has squeaked yet." }
+            }
+            @for squeak in &squeaks {
+                (squeak_article(squeak, &originals))
+            }
+        },
+    ))
+}
+
+/// A single squeak, which is what unfurls when a link is pasted into Discord
+pub async fn squeak_permalink(
+    State(state): State<AppState>,
+    Path(id): Path<ulid::Ulid>,
+) -> Result<Markup, OblivionServerError> {
+    let squeak = find_squeak(&state, id)
+        .await?
+        .ok_or(OblivionServerError::SqueakNotFound(id))?;
+    let originals = resqueaked_originals(&state, std::slice::from_ref(&squeak)).await?;
+
+    // A plain resqueak has nothing to say for itself, so preview what it resqueaked
+    let original = squeak.resqueak_of.and_then(|id| originals.get(&id));
+    let description = match original {
+        Some(original) if squeak.content.is_empty() => preview(&original.content),
+        _ => preview(&squeak.content),
+    };
+    let meta = Meta {
+        title: format!("{} on Skeever", squeak.author.name),
+        description,
+        image: Some(squeak.author.avatar_url.clone()),
+        url: format!("{}/s/{}", state.public_url, squeak.id),
+    };
+
+    Ok(layout(&meta, squeak_article(&squeak, &originals)))
+}
+
+/// A character's profile and their recent squeaks
+pub async fn character_page(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Markup, OblivionServerError> {
+    let character = characters::find_by_name(&state, &name)
+        .await?
+        .map(CharacterProfile::from)
+        .ok_or(OblivionServerError::CharacterNotFound(name))?;
+    let character = state.media.localize_profile(character).await;
+
+    let squeaks = recent_squeaks(&state, |squeak| squeak.is_by(&character.name)).await?;
+    let originals = resqueaked_originals(&state, &squeaks).await?;
+
+    let feed_url = format!(
+        "/feeds/characters/{}.atom",
+        encode_path_segment(&character.name)
+    );
+    let meta = Meta {
+        title: character.name.clone(),
+        description: preview(&character.description),
+        image: character.avatar_url.clone(),
+        url: format!(
+            "{}/c/{}",
+            state.public_url,
+            encode_path_segment(&character.name)
+        ),
+    };
+
+    Ok(layout(
+        &meta,
+        html! {
+            header {
+                div class="author" {
+                    @if let Some(avatar_url) = &character.avatar_url {
+                        img src=(avatar_url) alt="";
+                    }
+                    h1 { (character.name) }
+                }
+                p class="content" { (character.description) }
+                a href=(feed_url) { "Follow in a feed reader" }
+            }
+            @if squeaks.is_empty() {
+                p { (character.name) " hasn't squeaked recently." }
+            }
+            @for squeak in &squeaks {
+                (squeak_article(squeak, &originals))
+            }
+        },
+    ))
+}
+
+/// Finds a squeak by ID, using its timestamp to narrow down where in the stream it lives
+pub async fn find_squeak(
+    state: &AppState,
+    id: ulid::Ulid,
+) -> Result<Option<Squeak>, OblivionServerError> {
+    // Nothing we stored can have an ID from past what we can represent
+    let Some(created) = ulid_created_at(id) else {
+        return Ok(None);
+    };
+    let query = EventQuery {
+        since: created.checked_sub(PERMALINK_WINDOW),
+        until: created.checked_add(PERMALINK_WINDOW),
+        subjects: vec![Squeak::get_subject()],
+        limit: state.ws_config.historical_batch_size,
+        ..Default::default()
+    };
+    let mut history = HistoryReader::new(&state.event_stream, query)
+        .with_recent_events(&state.recent_events)
+        .with_replay_permits(&state.replay_permits)
+        .with_media(&state.media)
+        .with_revisions(&state.revisions)
+        .with_likes(&state.like_counts)
+        .with_batch_delay(Duration::ZERO);
+
+    loop {
+        let events = match history.next_page().await {
+            Ok(Some(events)) => events,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(OblivionServerError::FailedToReadEvents(e)),
+        };
+
+        let found = events.into_iter().find_map(|event| match event {
+            FeedEvent::Squeak(squeak) if squeak.id == id => Some(*squeak),
+            _ => None,
+        });
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+}
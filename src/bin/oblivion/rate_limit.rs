@@ -0,0 +1,271 @@
+use crate::{app_state::AppState, auth::Identity, error::OblivionServerError};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use oddbot::prelude::*;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// How many keys we track before forgetting the ones whose buckets have refilled, and then the
+/// ones we heard from least recently
+const MAX_TRACKED_KEYS: usize = 10_000;
+/// How many proxies append to `X-Forwarded-For` when we're told to trust it, a single reverse proxy
+const DEFAULT_TRUSTED_PROXY_HOPS: u64 = 1;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket per key, e.g. per client IP or per API token
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    burst: f64,
+    per_second: f64,
+    max_keys: usize,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K> RateLimiter<K>
+where
+    K: Hash + Eq,
+{
+    /// Creates a limiter allowing `burst` requests at once, refilling at `per_minute` (0 disables it)
+    pub fn new(burst: u64, per_minute: u64) -> Self {
+        Self {
+            burst: burst.max(1) as f64,
+            per_second: per_minute as f64 / 60.0,
+            max_keys: MAX_TRACKED_KEYS,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token for the key, or returns how long until one is available
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        if self.per_second == 0.0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() >= self.max_keys && !buckets.contains_key(&key) {
+            self.make_room(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+
+    /// Forgets buckets until there's room for a new key
+    fn make_room(&self, buckets: &mut HashMap<K, Bucket>, now: Instant) {
+        // A bucket that has refilled is the same as one we never saw
+        buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens + elapsed * self.per_second < self.burst
+        });
+        if buckets.len() < self.max_keys {
+            return;
+        }
+
+        // Everyone is mid-burst, so forget whoever we heard from least recently, clearing a
+        // quarter of the map so we don't do this on every request
+        let keep = self.max_keys - self.max_keys.div_ceil(4);
+        let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+        let evict = updated.len() - keep;
+        let (_, &mut cutoff, _) = updated.select_nth_unstable(evict - 1);
+        buckets.retain(|_, bucket| bucket.updated > cutoff);
+    }
+}
+
+/// Works out which IP a request came from, trusting `X-Forwarded-For` only when configured to
+fn client_ip(request: &Request, peer: SocketAddr) -> IpAddr {
+    if OddbotConfig::get_trust_forwarded_for() {
+        let hops = OddbotConfig::get_trusted_proxy_hops().unwrap_or(DEFAULT_TRUSTED_PROXY_HOPS);
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| forwarded_client(value, hops as usize));
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+
+    peer.ip()
+}
+
+/// Picks the client out of an `X-Forwarded-For` header that `hops` trusted proxies appended to
+///
+/// Clients can send the header themselves, so only the entries our proxies added, counting from the
+/// right, can be believed.
+fn forwarded_client(header: &str, hops: usize) -> Option<IpAddr> {
+    let entries: Vec<&str> = header.split(',').collect();
+    let index = entries.len().checked_sub(hops.max(1))?;
+    entries[index].trim().parse().ok()
+}
+
+/// Throttles requests per client IP
+pub async fn limit_by_ip(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, OblivionServerError> {
+    let ip = client_ip(&request, peer);
+    if let Err(retry_after) = state.ip_rate_limiter.check(ip) {
+        tracing::debug!("Rate limited {} for {:?}", ip, retry_after);
+        return Err(OblivionServerError::RateLimited(retry_after));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Throttles requests per API token, for routes behind the token middleware
+pub async fn limit_by_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, OblivionServerError> {
+    let Some(identity) = request.extensions().get::<Identity>() else {
+        return Ok(next.run(request).await);
+    };
+
+    if let Err(retry_after) = state.token_rate_limiter.check(identity.token_id.clone()) {
+        tracing::debug!(
+            "Rate limited token {} for {:?}",
+            identity.token_id,
+            retry_after
+        );
+        return Err(OblivionServerError::RateLimited(retry_after));
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked<K>(limiter: &RateLimiter<K>) -> usize {
+        limiter.buckets.lock().unwrap().len()
+    }
+
+    #[test]
+    fn allows_a_burst_then_limits() {
+        let limiter = RateLimiter::new(3, 60);
+
+        for _ in 0..3 {
+            assert!(limiter.check("a").is_ok());
+        }
+        let retry_after = limiter.check("a").unwrap_err();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn keys_have_their_own_buckets() {
+        let limiter = RateLimiter::new(1, 60);
+
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_err());
+        assert!(limiter.check("b").is_ok());
+    }
+
+    #[test]
+    fn refills_over_time() {
+        // 100 tokens a millisecond
+        let limiter = RateLimiter::new(1, 6_000_000);
+
+        assert!(limiter.check("a").is_ok());
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(limiter.check("a").is_ok());
+    }
+
+    #[test]
+    fn zero_per_minute_disables_limiting() {
+        let limiter = RateLimiter::new(1, 0);
+
+        for _ in 0..100 {
+            assert!(limiter.check("a").is_ok());
+        }
+        assert_eq!(tracked(&limiter), 0);
+    }
+
+    #[test]
+    fn forgets_refilled_buckets_when_full() {
+        let mut limiter = RateLimiter::new(2, 6_000_000);
+        limiter.max_keys = 4;
+
+        for key in 0..4 {
+            limiter.check(key).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(5));
+        limiter.check(4).unwrap();
+
+        assert_eq!(tracked(&limiter), 1);
+    }
+
+    #[test]
+    fn evicts_least_recently_seen_keys_when_nothing_has_refilled() {
+        let mut limiter = RateLimiter::new(1, 1);
+        limiter.max_keys = 4;
+
+        for key in 0..4 {
+            limiter.check(key).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // Hearing from 0 again, even though it's limited, makes 1 the oldest
+        assert!(limiter.check(0).is_err());
+        limiter.check(4).unwrap();
+
+        assert_eq!(tracked(&limiter), 4);
+        // 0 is still out of tokens, while 1 was forgotten so it gets a fresh burst
+        assert!(limiter.check(0).is_err());
+        assert!(limiter.check(1).is_ok());
+    }
+
+    #[test]
+    fn forwarded_clients_come_from_the_right() {
+        let client = "203.0.113.7".parse::<IpAddr>().unwrap();
+
+        assert_eq!(forwarded_client("203.0.113.7", 1), Some(client));
+        assert_eq!(forwarded_client("10.0.0.1, 203.0.113.7", 1), Some(client));
+        assert_eq!(forwarded_client("203.0.113.7, 192.0.2.10", 2), Some(client));
+    }
+
+    #[test]
+    fn spoofed_leading_entries_dont_change_the_client() {
+        let honest = forwarded_client("203.0.113.7", 1);
+        let spoofed = forwarded_client("198.51.100.1, 198.51.100.2, 203.0.113.7", 1);
+
+        assert_eq!(spoofed, honest);
+    }
+
+    #[test]
+    fn too_few_forwarded_entries_are_ignored() {
+        assert_eq!(forwarded_client("203.0.113.7", 2), None);
+        assert_eq!(forwarded_client("not an ip", 1), None);
+    }
+}
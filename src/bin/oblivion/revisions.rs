@@ -0,0 +1,156 @@
+use oddbot::{
+    event_stream::query::ulid_created_at,
+    feed::FeedEvent,
+    skeever::{revision::SqueakEdit, squeak::Squeak},
+};
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+use time::OffsetDateTime;
+
+/// What last happened to a published squeak
+#[derive(Clone, Debug)]
+enum Revision {
+    Edited(SqueakEdit),
+    Deleted,
+}
+
+/// When the oldest squeak younger than `max_age` was posted, if there's a limit
+pub fn horizon(max_age: Option<Duration>) -> Option<OffsetDateTime> {
+    // A max age reaching back before year -9999 is the same as no max age
+    let max_age = time::Duration::try_from(max_age?).ok()?;
+    OffsetDateTime::now_utc().checked_sub(max_age)
+}
+
+/// Whether a squeak was posted after the horizon, so what happened to it since is kept
+pub fn is_tracked(squeak_id: ulid::Ulid, horizon: Option<OffsetDateTime>) -> bool {
+    match (horizon, ulid_created_at(squeak_id)) {
+        (Some(horizon), Some(created)) => created >= horizon,
+        _ => true,
+    }
+}
+
+/// The latest edit or deletion of every recent squeak that had one
+///
+/// The forwarder task warms it from the event stream on start-up and keeps it current, so
+/// replays can show squeaks as they are now without reading ahead in the stream. Only squeaks
+/// younger than the max age are tracked, older ones are served as they were published.
+#[derive(Debug, Default)]
+pub struct Revisions {
+    inner: RwLock<HashMap<ulid::Ulid, Revision>>,
+    /// How old a squeak can be and still have its revisions kept, `None` keeping every one
+    max_age: Option<Duration>,
+}
+
+impl Revisions {
+    /// Creates an empty set of revisions, keeping them for squeaks up to `max_age` old
+    pub fn new(max_age: Option<Duration>) -> Self {
+        Self {
+            inner: RwLock::default(),
+            max_age,
+        }
+    }
+
+    /// When the oldest squeak we keep revisions for was posted, if there's a limit
+    pub fn horizon(&self) -> Option<OffsetDateTime> {
+        horizon(self.max_age)
+    }
+
+    /// Records an edit or deletion, ignoring every other event
+    pub fn record(&self, event: &FeedEvent) {
+        let (squeak_id, revision) = match event {
+            FeedEvent::SqueakEdit(edit) => (edit.squeak_id, Revision::Edited(edit.clone())),
+            FeedEvent::SqueakDelete(delete) => (delete.squeak_id, Revision::Deleted),
+            _ => return,
+        };
+        if !is_tracked(squeak_id, self.horizon()) {
+            return;
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        let newer = match (inner.get(&squeak_id), &revision) {
+            // Nothing brings a squeak back once it's deleted
+            (Some(Revision::Deleted), _) => false,
+            // Edits can be seen out of order while warming, the newest one wins
+            (Some(Revision::Edited(current)), Revision::Edited(edit)) => edit.id > current.id,
+            _ => true,
+        };
+        if newer {
+            inner.insert(squeak_id, revision);
+        }
+    }
+
+    /// Gets a squeak as it is now, or `None` if it was deleted
+    pub fn apply(&self, mut squeak: Box<Squeak>) -> Option<Box<Squeak>> {
+        match self.inner.read().unwrap().get(&squeak.id) {
+            Some(Revision::Deleted) => None,
+            Some(Revision::Edited(edit)) => {
+                squeak.apply_edit(edit);
+                Some(squeak)
+            }
+            None => Some(squeak),
+        }
+    }
+
+    /// Forgets the revisions of squeaks that have aged past the max age
+    pub fn evict_expired(&self) {
+        let horizon = self.horizon();
+        if horizon.is_none() {
+            return;
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        let before = inner.len();
+        inner.retain(|squeak_id, _| is_tracked(*squeak_id, horizon));
+        tracing::debug!("Evicted revisions of {} squeaks", before - inner.len());
+    }
+
+    /// How many squeaks were edited or deleted
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oddbot::skeever::revision::SqueakDelete;
+    use std::time::SystemTime;
+
+    fn squeak_id(age: Duration) -> ulid::Ulid {
+        ulid::Ulid::from_datetime(SystemTime::now() - age)
+    }
+
+    fn delete(squeak_id: ulid::Ulid) -> FeedEvent {
+        FeedEvent::SqueakDelete(SqueakDelete::new(squeak_id, "Ulfric".to_string()))
+    }
+
+    #[test]
+    fn ignores_revisions_of_squeaks_past_the_max_age() {
+        let revisions = Revisions::new(Some(Duration::from_secs(60 * 60)));
+
+        revisions.record(&delete(squeak_id(Duration::from_secs(60))));
+        revisions.record(&delete(squeak_id(Duration::from_secs(2 * 60 * 60))));
+
+        assert_eq!(revisions.len(), 1);
+    }
+
+    #[test]
+    fn keeps_everything_without_a_max_age() {
+        let revisions = Revisions::new(None);
+
+        revisions.record(&delete(squeak_id(Duration::from_secs(365 * 24 * 60 * 60))));
+        revisions.evict_expired();
+
+        assert_eq!(revisions.len(), 1);
+    }
+
+    #[test]
+    fn evicts_squeaks_that_aged_out() {
+        let revisions = Revisions::new(Some(Duration::from_millis(50)));
+
+        revisions.record(&delete(squeak_id(Duration::ZERO)));
+        std::thread::sleep(Duration::from_millis(100));
+        revisions.evict_expired();
+
+        assert_eq!(revisions.len(), 0);
+    }
+}
@@ -1,20 +1,59 @@
 use app_state::AppState;
+use auth::Identity;
 use axum::{
     Json, Router,
-    extract::{State, ws},
-    http::StatusCode,
+    extract::State,
+    http::{Extensions, HeaderMap, HeaderValue, Method, header},
+    middleware,
+    response::Response,
     routing::{get, post},
 };
 use error::OblivionServerError;
-use futures::{SinkExt, StreamExt};
-use oddbot::prelude::*;
-use serde::{Deserialize, Serialize};
+use extract::Query;
+use oddbot::{
+    event_stream::query::ulid_created_at,
+    feed::{
+        Topic,
+        protocol::{Encoding, PROTOCOL_VERSION},
+    },
+    prelude::*,
+};
+use serde::Deserialize;
+use stats::FeedStatsSnapshot;
+use std::{net::SocketAddr, time::Duration};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa::IntoParams;
+use websockets::{MAX_REPLAY_LIMIT, Protocol, ReplayRequest, Session};
 
 mod app_state;
+mod auth;
+mod broadcast;
+mod cache;
+mod characters;
 mod error;
+mod events;
+mod extract;
+mod feeds;
+mod history;
+mod likes;
+mod link;
+mod media;
+mod openapi;
+mod outbox;
+mod pages;
+mod rate_limit;
+mod request_id;
+mod revisions;
+mod sse;
+mod stats;
+mod telemetry;
+mod threads;
 mod websockets;
 
+/// How long we wait for connections to close on shutdown by default
+const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 10;
+
 #[tokio::main]
 async fn main() -> Result<(), OddbotError> {
     // Start the tracer
@@ -27,123 +66,330 @@ async fn main() -> Result<(), OddbotError> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // Install the metrics recorder before anything starts recording
+    let metrics = telemetry::install()
+        .map_err(|e| OddbotError::InvalidConfig(format!("failed to install metrics: {e}")))?;
+
     // Wrap our dependencies into the app state so routes can access them
-    let app_state = AppState::init().await?;
+    let app_state = AppState::init(metrics).await?;
 
     // Spawn a separate task that receives events from the event stream and forwards them to websockets
-    let event_sender = app_state.event_sender.clone();
-    let event_stream = app_state
-        .get_event_stream()
-        .await
-        .expect("Could not create connection to event stream");
-    tokio::spawn(async move {
-        websockets::forward_events_to_websockets(event_stream.clone(), event_sender).await;
-    });
+    let forwarder = tokio::spawn(websockets::forward_events_to_websockets(app_state.clone()));
+    let like_watcher = tokio::spawn(likes::watch_likes(app_state.clone()));
+    let character_watcher = tokio::spawn(characters::watch_characters(app_state.clone()));
+
+    // Routes that do real work are throttled per client IP
+    let limited = Router::new()
+        .route("/", get(pages::timeline))
+        .route("/s/{id}", get(pages::squeak_permalink))
+        .route("/c/{name}", get(pages::character_page))
+        .route(
+            "/ws",
+            get(ws_handler)
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    rate_limit::limit_by_token,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    auth::optional_token,
+                )),
+        )
+        .route("/sse", get(sse::sse_handler))
+        .route(
+            "/events",
+            post(events::save_event)
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    rate_limit::limit_by_token,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    auth::require_token,
+                )),
+        )
+        .route("/events", get(events::get_events))
+        .route("/link", post(link::redeem_link_code))
+        .route("/squeaks/{id}/thread", get(threads::get_thread))
+        .route("/squeaks/{id}/likes", get(likes::get_likes))
+        .route("/characters", get(characters::list_characters))
+        .route("/characters/{id}", get(characters::get_character))
+        .route(
+            "/characters/by-name/{name}",
+            get(characters::get_character_by_name),
+        )
+        .route("/feeds/skeever.atom", get(feeds::skeever_atom))
+        .route("/feeds/skeever.json", get(feeds::skeever_json))
+        .route("/feeds/characters/{file}", get(feeds::character_feed))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit::limit_by_ip,
+        ));
 
     // Initialize an axum server
     let router = Router::new()
         .route("/health", get(health_handler))
-        .route("/ws", get(ws_handler))
-        .route("/events", post(save_event))
-        .route("/events", get(get_events))
-        .with_state(app_state);
+        .route("/ready", get(ready_handler))
+        .route("/stats", get(stats_handler))
+        .route("/openapi.json", get(openapi::openapi_handler))
+        .route("/metrics", get(telemetry::metrics_handler))
+        // Media is immutable and loaded many times per page, so it isn't limited per IP
+        .route("/media/{hash}", get(media::media_handler))
+        .merge(limited)
+        .route_layer(middleware::from_fn(telemetry::track_http_requests))
+        .layer(middleware::from_fn(request_id::assign_request_id))
+        .layer(cors_layer())
+        .with_state(app_state.clone());
+
+    let addr = format!(
+        "{}:{}",
+        OddbotConfig::get_oblivion_server_host(),
+        OddbotConfig::get_oblivion_server_port()
+    );
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("Listening on {}", addr);
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, router).await.unwrap();
+    let shutdown = app_state.shutdown.clone();
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down, closing websockets and draining connections");
+        shutdown.cancel();
+    })
+    .await?;
+
+    // Give websockets and SSE streams a chance to say goodbye before we exit
+    let grace = Duration::from_secs(
+        OddbotConfig::get_shutdown_grace_secs().unwrap_or(DEFAULT_SHUTDOWN_GRACE_SECS),
+    );
+    app_state.connections.close();
+    if tokio::time::timeout(grace, app_state.connections.wait())
+        .await
+        .is_err()
+    {
+        tracing::warn!(
+            "{} connections still open after {:?}, exiting anyway",
+            app_state.connections.len(),
+            grace
+        );
+    }
+    if let Err(e) = forwarder.await {
+        tracing::error!("Event forwarder failed: {:?}", e);
+    }
+    if let Err(e) = like_watcher.await {
+        tracing::error!("Like watcher failed: {:?}", e);
+    }
+    if let Err(e) = character_watcher.await {
+        tracing::error!("Character watcher failed: {:?}", e);
+    }
 
+    tracing::info!("Shutdown complete");
     Ok(())
 }
 
-/// Returns OK for health checks
-async fn health_handler() -> &'static str {
-    "OK"
-}
+/// Lets the configured origins call us from a browser
+fn cors_layer() -> CorsLayer {
+    let origins = OddbotConfig::get_cors_allowed_origins();
+    let allow_origin = match origins.iter().any(|origin| origin == "*") {
+        true => AllowOrigin::any(),
+        false => AllowOrigin::list(origins.iter().filter_map(|origin| {
+            origin
+                .parse::<HeaderValue>()
+                .inspect_err(|_| tracing::warn!("Ignoring invalid CORS origin {}", origin))
+                .ok()
+        })),
+    };
 
-/// Handles incoming websocket requests
-async fn ws_handler(
-    ws: axum::extract::WebSocketUpgrade,
-    State(state): State<AppState>,
-) -> impl axum::response::IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            request_id::REQUEST_ID_HEADER.clone(),
+        ])
+        .expose_headers([header::RETRY_AFTER, request_id::REQUEST_ID_HEADER.clone()])
 }
 
-/// Handles individual websocket connections
-async fn handle_socket(socket: ws::WebSocket, state: AppState) {
-    let mut event_receiver = state.event_sender.subscribe();
-    let (sender, mut receiver) = socket.split();
-
-    // Send historical messages first and get the sender back
-    let mut sender = match websockets::send_historical_messages(
-        state
-            .get_event_stream()
-            .await
-            .expect("Could not create connection to event stream"),
-        sender,
-    )
-    .await
-    {
-        Ok(sender) => sender,
-        Err(e) => {
-            tracing::error!("Failed to send historical messages: {:?}", e);
-            return;
+/// Resolves once we're asked to stop, by Ctrl+C or a SIGTERM from the orchestrator
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
         }
     };
 
-    // Handle incoming messages in a separate task
-    let receiver_task = tokio::spawn(async move {
-        while let Some(Ok(message)) = receiver.next().await {
-            match message {
-                ws::Message::Text(text) => {
-                    tracing::debug!("Received text message: {}", text);
-                    // Handle any client messages if needed
-                }
-                ws::Message::Close(_) => break,
-                _ => continue,
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
             }
-        }
-    });
-
-    // Forward events to websocket
-    let sender_task = tokio::spawn(async move {
-        while let Ok(squeak) = event_receiver.recv().await {
-            let Ok(serialized_squeak) = serde_json::to_string(&squeak) else {
-                tracing::error!("Failed to serialize squeak from {:?}", &squeak.author.name);
-                tracing::debug!("Failed Squeak: {:?}", &squeak);
-                continue;
-            };
-            if sender
-                .send(ws::Message::Text(serialized_squeak.into()))
-                .await
-                .is_err()
-            {
-                break;
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
             }
-            tracing::debug!("Successfully squeaked {:?}", &squeak);
         }
-    });
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    // Wait for either task to finish
     tokio::select! {
-        _ = receiver_task => {},
-        _ = sender_task => {},
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Returns OK for health checks
+#[utoipa::path(get, path = "/health", tag = "server", responses((status = 200, body = String)))]
+async fn health_handler() -> &'static str {
+    "OK"
+}
+
+/// Returns OK once NATS is connected and the event stream exists
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "server",
+    responses(
+        (status = 200, body = String),
+        (status = 503, description = "Not ready to serve traffic", body = crate::error::ErrorBody),
+    )
+)]
+async fn ready_handler(State(state): State<AppState>) -> Result<&'static str, OblivionServerError> {
+    if state.shutdown.is_cancelled() {
+        return Err(OblivionServerError::NotReady("shutting down".into()));
+    }
+
+    let connection_state = state.nats_client.connection_state();
+    if connection_state != async_nats::connection::State::Connected {
+        return Err(OblivionServerError::NotReady(format!(
+            "NATS is {connection_state}"
+        )));
     }
 
-    tracing::info!("Websocket connection closed");
+    state.event_stream.check().await.map_err(|e| {
+        tracing::warn!("Readiness check failed: {:?}", e);
+        OblivionServerError::NotReady("event stream unavailable".into())
+    })?;
+
+    Ok("OK")
 }
 
-#[derive(Deserialize, Serialize)]
-struct Event;
+/// Returns the live feed counters
+#[utoipa::path(get, path = "/stats", tag = "server", responses((status = 200, body = FeedStatsSnapshot)))]
+async fn stats_handler(State(state): State<AppState>) -> Json<FeedStatsSnapshot> {
+    Json(state.feed_stats.snapshot())
+}
 
-/// Save Event
-#[axum::debug_handler]
-async fn save_event(Json(_payload): Json<Event>) -> Result<StatusCode, OblivionServerError> {
-    // TODO: Save events from various sources, like an oblivion mod
-    Ok(StatusCode::OK)
+/// Query parameters accepted when opening a websocket
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct WsParams {
+    /// Protocol version, clients that leave it out get the legacy squeak firehose
+    v: Option<u32>,
+    /// Comma-separated topics to subscribe to straight away
+    topics: Option<String>,
+    /// Resume from the event after this ULID instead of replaying recent history
+    after: Option<ulid::Ulid>,
+    /// RFC 3339 timestamp of the oldest event to replay
+    since: Option<String>,
+    /// Only replay the newest events, up to this many
+    limit: Option<usize>,
 }
 
-/// Get Events
-async fn get_events() -> Result<Json<Vec<Event>>, OblivionServerError> {
-    // TODO: Get events from the oblivion event stream
-    Ok(Json(vec![]))
+/// Handles incoming websocket requests
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "feed",
+    params(WsParams),
+    responses(
+        (status = 101, description = "Upgraded to a websocket speaking ClientFrame and ServerFrame, \
+            as JSON or as MessagePack or CBOR binary frames when the client asks for the \
+            `oblivion.v1.msgpack` or `oblivion.v1.cbor` subprotocol"),
+        (status = 400, description = "Invalid query", body = crate::error::ErrorBody),
+        (status = 401, description = "Missing or invalid API token", body = crate::error::ErrorBody),
+    )
+)]
+async fn ws_handler(
+    ws: axum::extract::WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(params): Query<WsParams>,
+    headers: HeaderMap,
+    extensions: Extensions,
+) -> Result<Response, OblivionServerError> {
+    let identity = extensions.get::<Identity>();
+    if OddbotConfig::get_ws_require_auth() && identity.is_none() {
+        return Err(OblivionServerError::Unauthorized);
+    }
+
+    // Asking for one of our subprotocols opts the client into the versioned protocol
+    let encoding = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|offered| offered.to_str().ok())
+        .and_then(Encoding::negotiate);
+
+    let protocol = match (params.v, encoding) {
+        (None, None) => Protocol::Legacy,
+        (None, Some(_)) | (Some(PROTOCOL_VERSION), _) => Protocol::V1,
+        (Some(v), _) => {
+            return Err(OblivionServerError::InvalidQuery(format!(
+                "unsupported protocol version: {v}"
+            )));
+        }
+    };
+
+    let topics = match params.topics {
+        Some(topics) => topics
+            .split(',')
+            .map(|topic| topic.trim().parse::<Topic>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| OblivionServerError::InvalidQuery(e.to_string()))?,
+        None => Vec::new(),
+    };
+
+    if params
+        .limit
+        .is_some_and(|limit| limit == 0 || limit > MAX_REPLAY_LIMIT)
+    {
+        return Err(OblivionServerError::InvalidQuery(format!(
+            "limit must be between 1 and {MAX_REPLAY_LIMIT}"
+        )));
+    }
+
+    // Resume cursors have to be placeable in time to seek the stream by them
+    if params
+        .after
+        .is_some_and(|after| ulid_created_at(after).is_none())
+    {
+        return Err(OblivionServerError::InvalidQuery(
+            "after is too far in the future".to_string(),
+        ));
+    }
+
+    let replay = ReplayRequest {
+        after: params.after,
+        since: params
+            .since
+            .as_deref()
+            .map(events::parse_timestamp)
+            .transpose()?,
+        limit: params.limit,
+    };
+
+    let session = Session::new(protocol, encoding.unwrap_or_default(), topics);
+    let connections = state.connections.clone();
+    let ws = match encoding {
+        Some(encoding) => ws.protocols([encoding.subprotocol()]),
+        None => ws,
+    };
+    Ok(ws.on_upgrade(move |socket| {
+        connections.track_future(websockets::handle_socket(socket, state, session, replay))
+    }))
 }
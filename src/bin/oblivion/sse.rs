@@ -0,0 +1,168 @@
+use crate::{
+    app_state::AppState, broadcast::BroadcastEvent, error::OblivionServerError,
+    history::HistoryReader, telemetry, websockets::ReplayRequest,
+};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{SinkExt, Stream, StreamExt, channel::mpsc};
+use oddbot::{feed::FeedEvent, prelude::*, skeever::squeak::Squeak};
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// How many events we buffer for an SSE client before waiting on it
+const SSE_BUFFER_SIZE: usize = 64;
+
+/// Streams squeaks as Server-Sent Events, resuming after `Last-Event-ID` when the browser reconnects
+#[utoipa::path(
+    get,
+    path = "/sse",
+    tag = "feed",
+    params(("Last-Event-ID" = Option<String>, Header, description = "ULID of the last squeak received")),
+    responses((status = 200, description = "`squeak` events carrying a Squeak", content_type = "text/event-stream", body = Squeak))
+)]
+pub async fn sse_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, OblivionServerError> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| ulid::Ulid::from_string(value).ok())
+                .ok_or_else(|| OblivionServerError::InvalidQuery("invalid Last-Event-ID".into()))
+        })
+        .transpose()?;
+
+    // Subscribe before replaying history so nothing slips through the gap
+    let event_receiver = state.event_sender.subscribe();
+    let (sender, receiver) = mpsc::channel(SSE_BUFFER_SIZE);
+    let connections = state.connections.clone();
+    connections.spawn(stream_squeaks(state, event_receiver, sender, last_event_id));
+
+    Ok(Sse::new(receiver.map(Ok)).keep_alive(KeepAlive::default()))
+}
+
+/// Feeds stored and then live squeaks into an SSE client's buffer until it goes away
+async fn stream_squeaks(
+    state: AppState,
+    mut event_receiver: broadcast::Receiver<Arc<BroadcastEvent>>,
+    mut sender: mpsc::Sender<Event>,
+    after: Option<ulid::Ulid>,
+) {
+    let mut last_sent = after;
+    let mut replayed_through =
+        match send_historical_squeaks(&state, &mut sender, &mut last_sent).await {
+            Ok(replayed_through) => replayed_through,
+            Err(e) => {
+                tracing::error!("Failed to send historical squeaks: {:?}", e);
+                return;
+            }
+        };
+
+    loop {
+        // Ending the stream lets the server drain this connection on shutdown
+        let event = tokio::select! {
+            event = event_receiver.recv() => event,
+            _ = state.shutdown.cancelled() => break,
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                // Fill the gap from the cache or stream instead of dropping the client
+                tracing::warn!("SSE client lagged by {} events, resyncing", skipped);
+                state.feed_stats.record_lag(skipped);
+                match send_historical_squeaks(&state, &mut sender, &mut last_sent).await {
+                    Ok(resynced_through) => {
+                        replayed_through = replayed_through.max(resynced_through);
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to resync lagging SSE client: {:?}", e);
+                        break;
+                    }
+                }
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let FeedEvent::Squeak(squeak) = event.event() else {
+            continue;
+        };
+        if replayed_through.is_some_and(|replayed_through| squeak.id <= replayed_through) {
+            continue;
+        }
+        let Some(sse_event) = squeak_event(squeak) else {
+            continue;
+        };
+        if sender.send(sse_event).await.is_err() {
+            break;
+        }
+        last_sent = last_sent.max(Some(squeak.id));
+        tracing::debug!("Successfully streamed squeak {}", squeak.id);
+    }
+
+    tracing::info!("SSE connection closed");
+}
+
+/// Sends stored squeaks after `last_sent`, returning the newest one replayed
+async fn send_historical_squeaks(
+    state: &AppState,
+    sender: &mut mpsc::Sender<Event>,
+    last_sent: &mut Option<ulid::Ulid>,
+) -> Result<Option<ulid::Ulid>, OddbotError> {
+    let started = std::time::Instant::now();
+    let replay = ReplayRequest {
+        after: *last_sent,
+        ..Default::default()
+    };
+    let config = &state.ws_config;
+    let query = config.history_query(&replay, vec![Squeak::get_subject()]);
+    let mut history = HistoryReader::new(&state.event_stream, query)
+        .with_recent_events(&state.recent_events)
+        .with_replay_permits(&state.replay_permits)
+        .with_media(&state.media)
+        .with_revisions(&state.revisions)
+        .with_likes(&state.like_counts)
+        .with_batch_delay(config.historical_batch_delay);
+    let mut replayed_through = None;
+
+    while let Some(events) = history.next_page().await? {
+        for event in events {
+            let FeedEvent::Squeak(squeak) = event else {
+                continue;
+            };
+            let Some(sse_event) = squeak_event(&squeak) else {
+                continue;
+            };
+            sender
+                .send(sse_event)
+                .await
+                .map_err(|e| OddbotError::WebsocketSend(e.to_string()))?;
+            replayed_through = replayed_through.max(Some(squeak.id));
+            *last_sent = (*last_sent).max(Some(squeak.id));
+        }
+    }
+
+    telemetry::record_replay("sse", started.elapsed());
+    Ok(replayed_through)
+}
+
+/// Turns a squeak into an SSE event whose ID the browser can resume from
+fn squeak_event(squeak: &Squeak) -> Option<Event> {
+    match Event::default()
+        .id(squeak.id.to_string())
+        .event("squeak")
+        .json_data(squeak)
+    {
+        Ok(event) => Some(event),
+        Err(e) => {
+            tracing::error!("Failed to serialize squeak {}: {}", squeak.id, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::telemetry;
+use metrics::counter;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use utoipa::ToSchema;
+
+/// Counters describing how well live clients are keeping up with the feed
+#[derive(Debug, Default)]
+pub struct FeedStats {
+    lags: AtomicU64,
+    lagged_events: AtomicU64,
+    evicted_clients: AtomicU64,
+}
+
+/// A point-in-time copy of the feed counters
+#[derive(Serialize, ToSchema)]
+pub struct FeedStatsSnapshot {
+    /// How many times a client fell behind the broadcast channel and had to resync
+    pub lags: u64,
+    /// How many broadcast events were skipped by lagging clients in total
+    pub lagged_events: u64,
+    /// How many clients were disconnected for not draining their outbox
+    pub evicted_clients: u64,
+}
+
+impl FeedStats {
+    /// Records a client falling behind by `skipped` events
+    pub fn record_lag(&self, skipped: u64) {
+        self.lags.fetch_add(1, Ordering::Relaxed);
+        self.lagged_events.fetch_add(skipped, Ordering::Relaxed);
+        counter!(telemetry::FEED_LAGS).increment(1);
+        counter!(telemetry::FEED_LAGGED_EVENTS).increment(skipped);
+    }
+
+    /// Records a stuck client being disconnected
+    pub fn record_eviction(&self) {
+        self.evicted_clients.fetch_add(1, Ordering::Relaxed);
+        counter!(telemetry::FEED_EVICTED_CLIENTS).increment(1);
+    }
+
+    pub fn snapshot(&self) -> FeedStatsSnapshot {
+        FeedStatsSnapshot {
+            lags: self.lags.load(Ordering::Relaxed),
+            lagged_events: self.lagged_events.load(Ordering::Relaxed),
+            evicted_clients: self.evicted_clients.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::app_state::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use oddbot::feed::FeedEvent;
+use std::time::{Duration, Instant};
+
+pub const WEBSOCKET_CONNECTIONS: &str = "oblivion_websocket_connections";
+pub const FEED_LAGS: &str = "oblivion_feed_lags_total";
+pub const FEED_LAGGED_EVENTS: &str = "oblivion_feed_lagged_events_total";
+pub const FEED_EVICTED_CLIENTS: &str = "oblivion_feed_evicted_clients_total";
+pub const BROADCAST_ERRORS: &str = "oblivion_broadcast_errors_total";
+pub const EVENTS_FORWARDED: &str = "oblivion_events_forwarded_total";
+pub const REPLAY_DURATION: &str = "oblivion_replay_duration_seconds";
+pub const NATS_ERRORS: &str = "oblivion_nats_errors_total";
+pub const HTTP_REQUEST_DURATION: &str = "oblivion_http_request_duration_seconds";
+
+/// How often histograms are compacted
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+/// Buckets for HTTP requests, which should be quick
+const HTTP_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Buckets for history replays, which may page through a lot of the stream
+const REPLAY_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Installs the global Prometheus recorder and keeps it tidy in the background
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(HTTP_REQUEST_DURATION.to_string()),
+            HTTP_BUCKETS,
+        )?
+        .set_buckets_for_metric(Matcher::Full(REPLAY_DURATION.to_string()), REPLAY_BUCKETS)?
+        .install_recorder()?;
+
+    let upkeep_handle = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep_handle.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Serves metrics in the Prometheus text format
+pub async fn metrics_handler(State(state): State<AppState>) -> String {
+    state.metrics.render()
+}
+
+/// Records how long each request took, labelled by route rather than raw path
+pub async fn track_http_requests(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    histogram!(
+        HTTP_REQUEST_DURATION,
+        "method" => method,
+        "route" => route,
+        "status" => response.status().as_u16().to_string(),
+    )
+    .record(started.elapsed());
+    response
+}
+
+/// Records a live event being handed to connected clients
+pub fn record_forwarded(event: &FeedEvent) {
+    let kind = match event {
+        FeedEvent::Squeak(_) => "squeak",
+        FeedEvent::SqueakEdit(_) => "squeak_edit",
+        FeedEvent::SqueakDelete(_) => "squeak_delete",
+        FeedEvent::SqueakLike(_) => "squeak_like",
+        FeedEvent::SqueakUnlike(_) => "squeak_unlike",
+        FeedEvent::GameEvent(_) => "game_event",
+    };
+    counter!(EVENTS_FORWARDED, "kind" => kind).increment(1);
+}
+
+/// Records a failed NATS operation, e.g. `fetch` or `ack`
+pub fn record_nats_error(operation: &'static str) {
+    counter!(NATS_ERRORS, "operation" => operation).increment(1);
+}
+
+/// Records how long a history replay took for a transport, e.g. `websocket` or `sse`
+pub fn record_replay(transport: &'static str, duration: Duration) {
+    histogram!(REPLAY_DURATION, "transport" => transport).record(duration);
+}
+
+/// Counts a websocket as connected for as long as it's alive
+pub struct ConnectionGuard;
+
+impl ConnectionGuard {
+    pub fn new() -> Self {
+        gauge!(WEBSOCKET_CONNECTIONS).increment(1);
+        Self
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        gauge!(WEBSOCKET_CONNECTIONS).decrement(1);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    app_state::AppState, error::OblivionServerError, extract::Path, history::HistoryReader, pages,
+};
+use axum::{Json, extract::State};
+use oddbot::{
+    event_stream::query::ulid_created_at, feed::FeedEvent, prelude::*, skeever::squeak::Squeak,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+/// Most squeaks we return for a single conversation
+const MAX_THREAD_SIZE: usize = 500;
+/// How long after a conversation started we look for replies to it
+const MAX_THREAD_SPAN: time::Duration = time::Duration::days(7);
+/// Most squeaks we look through for replies, whichever conversation they're part of
+const MAX_THREAD_SCAN: usize = 20_000;
+
+/// A conversation, as a tree of replies
+#[derive(Serialize, ToSchema)]
+pub struct Thread {
+    /// ID of the squeak that started the conversation
+    pub id: ulid::Ulid,
+    /// Top-level squeaks, which is just the first one unless it or a parent was deleted
+    pub squeaks: Vec<ThreadNode>,
+    /// Whether the conversation had more squeaks than we return, or was too buried among other
+    /// squeaks to read all of it
+    pub truncated: bool,
+}
+
+/// A squeak and the replies to it, oldest first
+#[derive(Serialize, ToSchema)]
+pub struct ThreadNode {
+    pub squeak: Squeak,
+    #[schema(no_recursion)]
+    pub replies: Vec<ThreadNode>,
+}
+
+/// Gets the conversation a squeak is part of
+///
+/// Replies posted more than a week after the conversation started aren't included.
+#[utoipa::path(
+    get,
+    path = "/squeaks/{id}/thread",
+    tag = "feed",
+    params(("id" = String, Path, description = "ULID of any squeak in the conversation")),
+    responses(
+        (status = 200, description = "The conversation as a tree of replies", body = Thread),
+        (status = 404, description = "No squeak with that ID", body = crate::error::ErrorBody),
+    )
+)]
+pub async fn get_thread(
+    State(state): State<AppState>,
+    Path(id): Path<ulid::Ulid>,
+) -> Result<Json<Thread>, OblivionServerError> {
+    let squeak = pages::find_squeak(&state, id)
+        .await?
+        .ok_or(OblivionServerError::SqueakNotFound(id))?;
+    let root = squeak.thread_root();
+
+    // Replies always come after the squeak that started the conversation, and conversations die
+    // down, so we only look so far past it
+    let started = ulid_created_at(root).unwrap_or_else(|| squeak.posted_at());
+    let until = started.checked_add(MAX_THREAD_SPAN);
+    let query = EventQuery {
+        since: Some(started),
+        until,
+        subjects: vec![Squeak::get_subject()],
+        limit: state.ws_config.historical_batch_size,
+        ..Default::default()
+    };
+    let mut history = HistoryReader::new(&state.event_stream, query)
+        .with_recent_events(&state.recent_events)
+        .with_replay_permits(&state.replay_permits)
+        .with_media(&state.media)
+        .with_revisions(&state.revisions)
+        .with_likes(&state.like_counts)
+        .with_batch_delay(Duration::ZERO);
+
+    let mut squeaks = Vec::new();
+    let mut scanned = 0;
+    let mut truncated = false;
+    'pages: while let Some(events) = history
+        .next_page()
+        .await
+        .map_err(OblivionServerError::FailedToReadEvents)?
+    {
+        for event in events {
+            let FeedEvent::Squeak(squeak) = event else {
+                continue;
+            };
+            scanned += 1;
+            if scanned > MAX_THREAD_SCAN {
+                truncated = true;
+                break 'pages;
+            }
+            if squeak.thread_root() != root {
+                continue;
+            }
+            if squeaks.len() == MAX_THREAD_SIZE {
+                truncated = true;
+                break 'pages;
+            }
+            squeaks.push(*squeak);
+        }
+    }
+
+    Ok(Json(Thread {
+        id: root,
+        squeaks: build_tree(squeaks),
+        truncated,
+    }))
+}
+
+/// Nests squeaks under the ones they reply to, keeping replies to missing squeaks at the top
+fn build_tree(squeaks: Vec<Squeak>) -> Vec<ThreadNode> {
+    let ids: HashSet<ulid::Ulid> = squeaks.iter().map(|squeak| squeak.id).collect();
+    let mut replies: HashMap<Option<ulid::Ulid>, Vec<Squeak>> = HashMap::new();
+    for squeak in squeaks {
+        let parent = squeak.parent_id.filter(|parent| ids.contains(parent));
+        replies.entry(parent).or_default().push(squeak);
+    }
+
+    fn nest(
+        parent: Option<ulid::Ulid>,
+        replies: &mut HashMap<Option<ulid::Ulid>, Vec<Squeak>>,
+    ) -> Vec<ThreadNode> {
+        replies
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|squeak| {
+                let id = squeak.id;
+                ThreadNode {
+                    squeak,
+                    replies: nest(Some(id), replies),
+                }
+            })
+            .collect()
+    }
+
+    nest(None, &mut replies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squeak(id: u128, parent: Option<u128>) -> Squeak {
+        serde_json::from_value(serde_json::json!({
+            "id": ulid::Ulid(id),
+            "content": "",
+            "author": { "name": "Ulfric", "avatar_url": "" },
+            "parent_id": parent.map(ulid::Ulid),
+            "thread_id": ulid::Ulid(1),
+        }))
+        .unwrap()
+    }
+
+    /// Flattens a tree into (id, depth) pairs, depth first
+    fn shape(nodes: &[ThreadNode], depth: usize, out: &mut Vec<(u128, usize)>) {
+        for node in nodes {
+            out.push((node.squeak.id.0, depth));
+            shape(&node.replies, depth + 1, out);
+        }
+    }
+
+    fn shape_of(squeaks: Vec<Squeak>) -> Vec<(u128, usize)> {
+        let mut out = Vec::new();
+        shape(&build_tree(squeaks), 0, &mut out);
+        out
+    }
+
+    #[test]
+    fn nests_replies_under_their_parents_in_order() {
+        let squeaks = vec![
+            squeak(1, None),
+            squeak(2, Some(1)),
+            squeak(3, Some(2)),
+            squeak(4, Some(1)),
+        ];
+
+        assert_eq!(shape_of(squeaks), vec![(1, 0), (2, 1), (3, 2), (4, 1)]);
+    }
+
+    #[test]
+    fn keeps_replies_to_missing_squeaks_at_the_top() {
+        // 2 was deleted, so its reply has nothing to hang off
+        let squeaks = vec![squeak(1, None), squeak(3, Some(2)), squeak(4, Some(3))];
+
+        assert_eq!(shape_of(squeaks), vec![(1, 0), (3, 0), (4, 1)]);
+    }
+
+    #[test]
+    fn empty_conversations_have_no_nodes() {
+        assert!(build_tree(Vec::new()).is_empty());
+    }
+}
@@ -1,20 +1,55 @@
+use crate::{
+    app_state::AppState,
+    broadcast::{self, BroadcastEvent},
+    history::{self, HistoryReader},
+    outbox::Outbox,
+    telemetry,
+};
 use async_nats::jetstream;
-use axum::extract::ws::{Message, WebSocket};
-use futures::{SinkExt, StreamExt, stream::SplitSink};
-use oddbot::{error::OddbotError, prelude::EventStream, skeever::squeak::Squeak};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::broadcast;
-
-pub async fn forward_events_to_websockets(
-    listener: Arc<EventStream>,
-    event_sender: broadcast::Sender<Squeak>,
-) {
-    let skeever_subject = Squeak::get_subject();
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
+use futures::StreamExt;
+use metrics::counter;
+use oddbot::{
+    error::OddbotError,
+    feed::{
+        FeedEvent, Topic,
+        protocol::{ClientFrame, Encoding, PROTOCOL_VERSION, ServerFrame},
+    },
+    game::event::GameEvent,
+    prelude::*,
+    skeever::{
+        revision::{SqueakDelete, SqueakEdit},
+        squeak::Squeak,
+    },
+};
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::{sync::broadcast::error::RecvError, time::Instant};
+
+/// How often we ping clients speaking the versioned protocol
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a client may stay silent before we consider it gone
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(90);
+/// How long to wait before fetching again after NATS returned an error
+const FETCH_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How often we forget the revisions and likes of squeaks that aged out
+const REVISION_EVICT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Forwards live events from the event stream to the recent event cache and every websocket
+pub async fn forward_events_to_websockets(state: AppState) {
+    let listener = &state.event_stream;
+    let event_sender = &state.event_sender;
 
     let consumer = match listener
         .create_consumer(
-            Some("oblivion_websocket_main_consumer".to_string()),
-            skeever_subject.clone(),
+            Some("oblivion_websocket_feed_consumer".to_string()),
+            vec![
+                Squeak::get_subject_filter(),
+                GameEvent::get_subject_filter(),
+            ],
             Some(jetstream::consumer::DeliverPolicy::New),
         )
         .await
@@ -26,48 +61,160 @@ pub async fn forward_events_to_websockets(
         }
     };
 
-    loop {
-        let Ok(mut messages) = consumer.fetch().max_messages(20).messages().await else {
-            tracing::error!("Failed to fetch messages");
-            continue;
-        };
+    // Live events are already being captured by the consumer, so we can't miss any while warming
+    warm_revisions(&state).await;
+    warm_recent_events(&state).await;
 
-        while let Some(message) = messages.next().await {
-            let Ok(message) = message else {
-                tracing::error!("Failed to receive message");
-                continue;
-            };
+    let mut next_eviction = Instant::now() + REVISION_EVICT_INTERVAL;
+    while !state.shutdown.is_cancelled() {
+        if Instant::now() >= next_eviction {
+            state.revisions.evict_expired();
+            state.like_counts.evict_expired();
+            next_eviction = Instant::now() + REVISION_EVICT_INTERVAL;
+        }
 
-            let Ok(squeak) = serde_json::from_slice::<Squeak>(&message.payload) else {
-                tracing::error!("Failed to deserialize event");
+        let mut messages = match consumer.fetch().max_messages(20).messages().await {
+            Ok(messages) => messages,
+            Err(e) => {
+                tracing::error!("Failed to fetch messages: {}", e);
+                telemetry::record_nats_error("fetch");
+                // Back off instead of hammering NATS while it's unavailable
+                tokio::select! {
+                    _ = tokio::time::sleep(FETCH_RETRY_DELAY) => {}
+                    _ = state.shutdown.cancelled() => {}
+                }
                 continue;
-            };
+            }
+        };
 
-            tracing::debug!("Successfully deserialized squeak {:?}", squeak);
-            match event_sender.receiver_count() {
-                0 => {
-                    tracing::trace!("No active websocket connections, ignore squeak");
+        while let Some(message) = messages.next().await {
+            let message = match message {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::error!("Failed to receive message: {}", e);
+                    telemetry::record_nats_error("receive");
+                    continue;
                 }
-                n => match event_sender.send(squeak) {
-                    Ok(_) => {
-                        tracing::debug!("Successfully broadcast event to {} receivers", n);
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to broadcast event: {}", e);
-                    }
-                },
             };
 
+            match FeedEvent::from_message(&message.subject, &message.payload) {
+                Ok(Some(event)) => {
+                    tracing::debug!("Successfully deserialized feed event {:?}", event);
+                    let event = state.media.localize_event_cached(event).await;
+                    telemetry::record_forwarded(&event);
+                    state.revisions.record(&event);
+                    state.recent_events.push(event.clone());
+                    match event_sender.receiver_count() {
+                        0 => {
+                            tracing::trace!("No active websocket connections, ignore event");
+                        }
+                        n => match event_sender.send(BroadcastEvent::new(event)) {
+                            Ok(_) => {
+                                tracing::debug!("Successfully broadcast event to {} receivers", n);
+                            }
+                            Err(e) => {
+                                tracing::error!("Failed to broadcast event: {}", e);
+                                counter!(telemetry::BROADCAST_ERRORS).increment(1);
+                            }
+                        },
+                    };
+                }
+                Ok(None) => {
+                    tracing::trace!("Ignoring event on subject {}", message.subject);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to deserialize event: {}", e);
+                }
+            }
+
             if let Err(e) = message.ack().await {
                 tracing::error!("Failed to ack message: {}", e);
+                telemetry::record_nats_error("ack");
             }
         }
 
         // Add a small delay between fetches
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {}
+            _ = state.shutdown.cancelled() => {}
+        }
+    }
+
+    tracing::info!("Stopped forwarding events to websockets");
+}
+
+/// Loads the edits and deletions of recent squeaks so replays can apply them
+async fn warm_revisions(state: &AppState) {
+    // Squeaks are always edited or deleted after they're posted, so older revisions are for
+    // squeaks we don't track
+    let query = EventQuery {
+        since: state.revisions.horizon(),
+        subjects: vec![SqueakEdit::get_subject(), SqueakDelete::get_subject()],
+        limit: state.ws_config.historical_batch_size,
+        ..Default::default()
+    };
+    let mut history =
+        HistoryReader::new(&state.event_stream, query).with_batch_delay(Duration::ZERO);
+
+    loop {
+        match history.next_page().await {
+            Ok(Some(events)) => events
+                .iter()
+                .for_each(|event| state.revisions.record(event)),
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("Failed to load squeak edits and deletions: {:?}", e);
+                return;
+            }
+        }
     }
+
+    tracing::info!(
+        "Loaded edits and deletions for {} squeaks",
+        state.revisions.len()
+    );
 }
 
+/// Seeds the recent event cache with the history window new clients get replayed
+async fn warm_recent_events(state: &AppState) {
+    let Some(max_age) = state.ws_config.historical_max_age else {
+        tracing::debug!("No history window configured, recent event cache starts cold");
+        return;
+    };
+
+    let since = OffsetDateTime::now_utc() - max_age;
+    let query = EventQuery {
+        since: Some(since),
+        subjects: vec![
+            Squeak::get_subject_filter(),
+            GameEvent::get_subject_filter(),
+        ],
+        limit: state.ws_config.historical_batch_size,
+        ..Default::default()
+    };
+    let mut history = HistoryReader::new(&state.event_stream, query)
+        .with_media(&state.media)
+        .with_batch_delay(Duration::ZERO);
+    let mut stored = Vec::new();
+
+    loop {
+        match history.next_page().await {
+            Ok(Some(events)) => stored.extend(events),
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("Failed to warm recent event cache: {:?}", e);
+                return;
+            }
+        }
+    }
+
+    tracing::info!("Warmed recent event cache with {} events", stored.len());
+    state.recent_events.warm(stored, since);
+}
+
+/// Most events a client may ask to have replayed with `?limit=`
+pub const MAX_REPLAY_LIMIT: usize = 1000;
+
 #[derive(Debug, Clone)]
 pub struct WebSocketConfig {
     pub historical_batch_size: usize,
@@ -85,56 +232,437 @@ impl Default for WebSocketConfig {
     }
 }
 
-pub async fn send_historical_messages(
-    listener: Arc<EventStream>,
-    mut ws_sender: SplitSink<WebSocket, Message>,
-) -> Result<SplitSink<WebSocket, Message>, OddbotError> {
-    let skeever_subject = Squeak::get_subject();
-    let batch_size = 100;
+impl WebSocketConfig {
+    /// Loads the config from the environment, falling back to the defaults
+    pub fn from_env() -> Self {
+        let default = Self::default();
 
-    let temp_consumer = listener
-        .create_consumer(
-            None,
-            skeever_subject,
-            Some(jetstream::consumer::DeliverPolicy::All),
-        )
-        .await?;
+        Self {
+            historical_batch_size: OddbotConfig::get_ws_history_batch_size()
+                .map_or(default.historical_batch_size, |size| size.max(1) as usize),
+            historical_max_age: match OddbotConfig::get_ws_history_max_age_secs() {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => default.historical_max_age,
+            },
+            historical_batch_delay: OddbotConfig::get_ws_history_batch_delay_ms()
+                .map_or(default.historical_batch_delay, Duration::from_millis),
+        }
+    }
+
+    /// Builds the event stream query for a replay request
+    pub fn history_query(&self, request: &ReplayRequest, subjects: Vec<String>) -> EventQuery {
+        // Resuming clients know exactly where they left off, everyone else gets a bounded window
+        // A max age reaching back before year -9999 is the same as no max age
+        let oldest = match (request.after, self.historical_max_age) {
+            (None, Some(max_age)) => time::Duration::try_from(max_age)
+                .ok()
+                .and_then(|max_age| OffsetDateTime::now_utc().checked_sub(max_age)),
+            _ => None,
+        };
+
+        EventQuery {
+            after: request.after.map(EventCursor::Id),
+            since: oldest.max(request.since),
+            subjects,
+            limit: self.historical_batch_size,
+            ..Default::default()
+        }
+    }
+}
+
+/// What a client asked to have replayed from history
+#[derive(Clone, Debug, Default)]
+pub struct ReplayRequest {
+    /// Only replay events after this ULID
+    pub after: Option<ulid::Ulid>,
+    /// Only replay events published at or after this time
+    pub since: Option<OffsetDateTime>,
+    /// Only replay the newest events, up to this many
+    pub limit: Option<usize>,
+}
+
+/// Which wire format a websocket client speaks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Raw squeak JSON, kept for clients that predate the versioned protocol
+    Legacy,
+    /// Typed frames from [`oddbot::feed::protocol`]
+    V1,
+}
+
+/// Per-connection state for a websocket client
+pub struct Session {
+    protocol: Protocol,
+    encoding: Encoding,
+    topics: HashSet<Topic>,
+    /// The newest event replayed from history, so we don't send it again live
+    replayed_through: Option<ulid::Ulid>,
+    /// The newest event sent to the client, where we resync from if it lags
+    last_sent: Option<ulid::Ulid>,
+}
+
+impl Session {
+    /// Creates a new session, defaulting to the skeever topic
+    pub fn new(protocol: Protocol, encoding: Encoding, topics: Vec<Topic>) -> Self {
+        let topics = match topics.is_empty() {
+            true => HashSet::from([Topic::Skeever]),
+            false => topics.into_iter().collect(),
+        };
+
+        Self {
+            protocol,
+            encoding,
+            topics,
+            replayed_through: None,
+            last_sent: None,
+        }
+    }
+
+    /// Checks whether a live event was already sent while replaying history
+    fn already_sent(&self, event: &FeedEvent) -> bool {
+        self.replayed_through
+            .is_some_and(|replayed_through| event.id() <= replayed_through)
+    }
+
+    /// Checks whether the client is interested in an event
+    fn wants(&self, event: &FeedEvent) -> bool {
+        match self.protocol {
+            Protocol::Legacy => matches!(event, FeedEvent::Squeak(_)),
+            Protocol::V1 => self.topics.iter().any(|topic| event.matches(topic)),
+        }
+    }
+
+    /// Gets the event stream subjects covering the client's topics
+    fn subject_filters(&self) -> Vec<String> {
+        let filters: HashSet<String> = match self.protocol {
+            Protocol::Legacy => HashSet::from([Squeak::get_subject()]),
+            Protocol::V1 => self
+                .topics
+                .iter()
+                .flat_map(|topic| topic.get_subject_filter())
+                .collect(),
+        };
+
+        filters.into_iter().collect()
+    }
+
+    /// Gets the client's topics in a stable order
+    fn topics(&self) -> Vec<Topic> {
+        let mut topics: Vec<Topic> = self.topics.iter().cloned().collect();
+        topics.sort_by_key(|topic| topic.to_string());
+        topics
+    }
+
+    /// Reads a frame sent by the client, as JSON text or binary in the negotiated encoding
+    fn decode_frame(&self, message: &Message) -> Option<Result<ClientFrame, OddbotError>> {
+        let frame = match message {
+            Message::Text(text) => serde_json::from_str(text).map_err(OddbotError::from),
+            Message::Binary(bytes) if self.encoding.is_binary() => {
+                self.encoding.decode(bytes).map_err(OddbotError::from)
+            }
+            _ => return None,
+        };
+
+        Some(frame)
+    }
+}
+
+/// Sends a protocol frame to the client in its negotiated encoding
+async fn send_frame(
+    outbox: &mut Outbox,
+    session: &Session,
+    frame: &ServerFrame,
+) -> Result<(), OddbotError> {
+    outbox
+        .send(broadcast::to_message(session.encoding, frame)?)
+        .await
+}
+
+/// Handles an individual websocket connection
+pub async fn handle_socket(
+    socket: WebSocket,
+    state: AppState,
+    mut session: Session,
+    replay: ReplayRequest,
+) {
+    let _connection = telemetry::ConnectionGuard::new();
+    let mut event_receiver = state.event_sender.subscribe();
+    let (sender, mut receiver) = socket.split();
+    let mut outbox = Outbox::new(sender);
+
+    if session.protocol == Protocol::V1 {
+        let hello = ServerFrame::Hello {
+            version: PROTOCOL_VERSION,
+            topics: session.topics(),
+        };
+        if let Err(e) = send_frame(&mut outbox, &session, &hello).await {
+            tracing::error!("Failed to greet websocket client: {:?}", e);
+            return;
+        }
+    }
+
+    // Send historical messages first
+    if let Err(e) = send_historical_messages(&state, &mut outbox, &mut session, &replay).await {
+        tracing::error!("Failed to send historical messages: {:?}", e);
+        close_socket(&state, outbox, None).await;
+        return;
+    }
+
+    let mut heartbeat =
+        tokio::time::interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    let mut close_frame = None;
 
     loop {
-        let mut messages = temp_consumer
-            .fetch()
-            .max_messages(batch_size)
-            .messages()
-            .await?;
+        tokio::select! {
+            message = receiver.next() => {
+                let Some(Ok(message)) = message else {
+                    break;
+                };
+                last_seen = Instant::now();
 
-        let mut batch_count = 0;
-        while let Some(message) = messages.next().await {
-            let Ok(message) = message else {
-                tracing::error!("Failed to fetch message");
-                continue;
-            };
-            let squeak = serde_json::from_slice::<Squeak>(&message.payload)?;
+                if let Message::Close(_) = message {
+                    break;
+                }
+                if session.protocol == Protocol::Legacy {
+                    tracing::debug!("Received message from legacy client: {:?}", message);
+                    continue;
+                }
 
-            ws_sender
-                .send(Message::Text(serde_json::to_string(&squeak)?.into()))
-                .await
-                .map_err(|e| OddbotError::WebsocketSend(e.to_string()))?;
+                let result = match session.decode_frame(&message) {
+                    Some(Ok(frame)) => {
+                        handle_client_frame(frame, &mut session, &mut outbox, &state).await
+                    }
+                    Some(Err(e)) => {
+                        let error = ServerFrame::Error {
+                            message: format!("Invalid frame: {e}"),
+                        };
+                        send_frame(&mut outbox, &session, &error).await
+                    }
+                    None => continue,
+                };
+                if let Err(e) = result {
+                    tracing::error!("Failed to handle client frame: {:?}", e);
+                    break;
+                }
+            }
+            event = event_receiver.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        // Fill the gap from the cache or stream instead of dropping the client
+                        tracing::warn!("Websocket client lagged by {} events, resyncing", skipped);
+                        state.feed_stats.record_lag(skipped);
+                        let resync = ReplayRequest {
+                            after: session.last_sent,
+                            ..Default::default()
+                        };
+                        if let Err(e) =
+                            send_historical_messages(&state, &mut outbox, &mut session, &resync)
+                                .await
+                        {
+                            tracing::error!("Failed to resync lagging client: {:?}", e);
+                            break;
+                        }
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if session.already_sent(event.event()) || !session.wants(event.event()) {
+                    continue;
+                }
 
-            message
-                .ack()
-                .await
-                .map_err(|e| OddbotError::WebsocketSend(e.to_string()))?; // Convert ack error
+                let event_id = event.event().id();
+                let message = match event.encoded(session.protocol, session.encoding) {
+                    Ok(Some(message)) => message,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        tracing::error!("Failed to serialize event {}: {:?}", event_id, e);
+                        continue;
+                    }
+                };
+                if outbox.send(message).await.is_err() {
+                    break;
+                }
+                session.last_sent = session.last_sent.max(Some(event_id));
+                tracing::debug!("Successfully sent event {}", event_id);
+            }
+            _ = state.shutdown.cancelled() => {
+                close_frame = Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "server shutting down".into(),
+                });
+                break;
+            }
+            _ = heartbeat.tick(), if session.protocol == Protocol::V1 => {
+                if last_seen.elapsed() > HEARTBEAT_TIMEOUT {
+                    tracing::info!("Websocket client missed its heartbeats, closing");
+                    close_frame = Some(CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "heartbeat timeout".into(),
+                    });
+                    break;
+                }
+                let ping = ServerFrame::Ping { nonce: None };
+                if send_frame(&mut outbox, &session, &ping).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
 
-            batch_count += 1;
+    close_socket(&state, outbox, close_frame).await;
+    tracing::info!("Websocket connection closed");
+}
+
+/// Closes a client's socket, evicting it with a close code if it stopped draining its outbox
+async fn close_socket(state: &AppState, outbox: Outbox, close_frame: Option<CloseFrame>) {
+    let close_frame = match outbox.is_stuck() {
+        true => {
+            tracing::warn!("Evicting websocket client that stopped reading");
+            state.feed_stats.record_eviction();
+            Some(CloseFrame {
+                code: close_code::AGAIN,
+                reason: "client too slow".into(),
+            })
+        }
+        false => close_frame,
+    };
+
+    if let Some(close_frame) = close_frame {
+        outbox.close(close_frame).await;
+    }
+}
+
+/// Applies a frame sent by a versioned client
+async fn handle_client_frame(
+    frame: ClientFrame,
+    session: &mut Session,
+    outbox: &mut Outbox,
+    state: &AppState,
+) -> Result<(), OddbotError> {
+    match frame {
+        ClientFrame::Subscribe { topics } => {
+            session.topics.extend(topics);
+            let topics = session.topics();
+            send_frame(outbox, session, &ServerFrame::Subscribed { topics }).await
+        }
+        ClientFrame::Unsubscribe { topics } => {
+            for topic in &topics {
+                session.topics.remove(topic);
+            }
+            let topics = session.topics();
+            send_frame(outbox, session, &ServerFrame::Unsubscribed { topics }).await
+        }
+        ClientFrame::Resume { after } => {
+            let replay = ReplayRequest {
+                after: Some(after),
+                ..Default::default()
+            };
+            let replayed = send_historical_messages(state, outbox, session, &replay).await?;
+            send_frame(outbox, session, &ServerFrame::Resumed { after, replayed }).await
+        }
+        ClientFrame::Ping { nonce } => {
+            send_frame(outbox, session, &ServerFrame::Pong { nonce }).await
+        }
+        ClientFrame::Pong { .. } => Ok(()),
+    }
+}
+
+/// Replays stored events matching the session to the client, returning how many were sent
+pub async fn send_historical_messages(
+    state: &AppState,
+    outbox: &mut Outbox,
+    session: &mut Session,
+    replay: &ReplayRequest,
+) -> Result<usize, OddbotError> {
+    let started = Instant::now();
+    let config = &state.ws_config;
+    let query = config.history_query(replay, session.subject_filters());
+
+    // Only the newest events are wanted, so read them from the end of the stream unless they're cached
+    let tail_limit = match (replay.limit, replay.after) {
+        (Some(limit), None) if !state.recent_events.covers(&query) => Some(limit),
+        _ => None,
+    };
+    if let Some(limit) = tail_limit {
+        let mut replayed = 0;
+        let newest = history::read_newest(
+            state,
+            query,
+            limit,
+            config.historical_batch_delay,
+            |event| session.wants(event),
+        )
+        .await?;
+        for event in newest {
+            if send_replayed_event(outbox, session, event).await? {
+                replayed += 1;
+            }
         }
+        telemetry::record_replay("websocket", started.elapsed());
+        return Ok(replayed);
+    }
+
+    let mut history = HistoryReader::new(&state.event_stream, query)
+        .with_recent_events(&state.recent_events)
+        .with_replay_permits(&state.replay_permits)
+        .with_media(&state.media)
+        .with_revisions(&state.revisions)
+        .with_likes(&state.like_counts)
+        .with_batch_delay(config.historical_batch_delay);
+
+    // With a limit we only want the newest events, so hold on to a sliding window until the end
+    let mut newest = replay.limit.map(VecDeque::with_capacity);
+    let mut replayed = 0;
 
-        if batch_count < batch_size {
-            break; // No more messages
+    while let Some(events) = history.next_page().await? {
+        for event in events {
+            if !session.wants(&event) {
+                continue;
+            }
+
+            match (&mut newest, replay.limit) {
+                (Some(newest), Some(limit)) => {
+                    if newest.len() == limit {
+                        newest.pop_front();
+                    }
+                    newest.push_back(event);
+                }
+                _ => {
+                    if send_replayed_event(outbox, session, event).await? {
+                        replayed += 1;
+                    }
+                }
+            }
         }
+    }
 
-        // Optional: Add a small delay between batches to prevent overwhelming the client
-        tokio::time::sleep(Duration::from_millis(50)).await;
+    for event in newest.into_iter().flatten() {
+        if send_replayed_event(outbox, session, event).await? {
+            replayed += 1;
+        }
     }
 
-    Ok(ws_sender)
+    telemetry::record_replay("websocket", started.elapsed());
+    Ok(replayed)
+}
+
+/// Sends a single replayed event, returning whether the client's format could carry it
+async fn send_replayed_event(
+    outbox: &mut Outbox,
+    session: &mut Session,
+    event: FeedEvent,
+) -> Result<bool, OddbotError> {
+    let event_id = event.id();
+    let Some(message) = broadcast::encode_event(session.protocol, session.encoding, &event)? else {
+        return Ok(false);
+    };
+
+    outbox.send(message).await?;
+    session.replayed_through = session.replayed_through.max(Some(event_id));
+    session.last_sent = session.last_sent.max(Some(event_id));
+
+    Ok(true)
 }
@@ -1,4 +1,11 @@
-use oddbot::{db, discord::character::CharacterStore, nats::create_nats_client, prelude::*};
+use oddbot::{
+    auth::link::LinkCodeStore,
+    db,
+    discord::character::CharacterStore,
+    nats::create_nats_client,
+    prelude::*,
+    skeever::{like::LikeStore, origin::SqueakOriginStore, resqueak::ResqueakStore},
+};
 use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -15,6 +22,7 @@ async fn main() -> Result<(), OddbotError> {
 
     // Connect to our database
     let pool = Arc::new(db::create_db_pool().await?);
+    db::run_migrations(&pool).await?;
 
     // Connect to our event stream
     let event_stream_name = OddbotConfig::get_event_stream_name();
@@ -33,10 +41,23 @@ async fn main() -> Result<(), OddbotError> {
 
     // Connect to our Oblivion character store
     let character_nats = create_nats_client().await?;
-    let character_store = Arc::new(CharacterStore::new(character_nats).await?);
+    let character_store = Arc::new(CharacterStore::new(character_nats.clone()).await?);
+    let link_store = Arc::new(LinkCodeStore::new(character_nats.clone()).await?);
+    let origin_store = Arc::new(SqueakOriginStore::new(character_nats.clone()).await?);
+    let like_store = Arc::new(LikeStore::new(character_nats.clone()).await?);
+    let resqueak_store = Arc::new(ResqueakStore::new(character_nats).await?);
 
     // Initialize our bot
-    let mut oddbot = DiscordBot::init(pool, event_stream, character_store).await?;
+    let mut oddbot = DiscordBot::init(
+        pool,
+        event_stream,
+        character_store,
+        link_store,
+        origin_store,
+        like_store,
+        resqueak_store,
+    )
+    .await?;
 
     // Finally, start a single shard, and start listening to events.
     //
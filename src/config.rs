@@ -31,6 +31,16 @@ impl OddbotConfig {
         Self::parse_optional_u64("OBLIVION_SOCIAL_ROLE_ID")
     }
 
+    /// Get the emoji that likes a squeak when reacting with it, a unicode emoji or a custom emoji's name
+    pub fn get_like_emoji() -> Option<String> {
+        std::env::var("SKEEVER_LIKE_EMOJI").ok()
+    }
+
+    /// Get the emoji that resqueaks a squeak when reacting with it, a unicode emoji or a custom emoji's name
+    pub fn get_resqueak_emoji() -> Option<String> {
+        std::env::var("SKEEVER_RESQUEAK_EMOJI").ok()
+    }
+
     /// Get the event stream name
     pub fn get_event_stream_name() -> Option<String> {
         std::env::var("EVENT_STREAM_NAME").ok()
@@ -41,6 +51,136 @@ impl OddbotConfig {
         std::env::var("EVENT_STREAM_PREFIX").ok()
     }
 
+    /// Get how many historical events are fetched per batch when a websocket connects
+    pub fn get_ws_history_batch_size() -> Option<u64> {
+        Self::parse_optional_u64("WS_HISTORY_BATCH_SIZE")
+    }
+
+    /// Get the oldest historical event replayed to a websocket, in seconds (0 disables the limit)
+    pub fn get_ws_history_max_age_secs() -> Option<u64> {
+        Self::parse_optional_u64("WS_HISTORY_MAX_AGE_SECS")
+    }
+
+    /// Get the delay between historical batches sent to a websocket, in milliseconds
+    pub fn get_ws_history_batch_delay_ms() -> Option<u64> {
+        Self::parse_optional_u64("WS_HISTORY_BATCH_DELAY_MS")
+    }
+
+    /// Get whether websocket clients must present an API token
+    pub fn get_ws_require_auth() -> bool {
+        std::env::var("WS_REQUIRE_AUTH").is_ok_and(|value| value == "true" || value == "1")
+    }
+
+    /// Get how old a squeak can be for oblivion-server to keep its edits, deletions and likes in memory,
+    /// in seconds (0 keeps every one)
+    pub fn get_revisions_max_age_secs() -> Option<u64> {
+        Self::parse_optional_u64("REVISIONS_MAX_AGE_SECS")
+    }
+
+    /// Get how many recent events oblivion-server keeps in memory
+    pub fn get_recent_events_capacity() -> Option<u64> {
+        Self::parse_optional_u64("RECENT_EVENTS_CAPACITY")
+    }
+
+    /// Get the address oblivion-server listens on
+    pub fn get_oblivion_server_host() -> String {
+        std::env::var("OBLIVION_SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string())
+    }
+
+    /// Get the port oblivion-server listens on
+    pub fn get_oblivion_server_port() -> u16 {
+        std::env::var("OBLIVION_SERVER_PORT").map_or(3000, |port| {
+            port.parse()
+                .unwrap_or_else(|_| panic!("OBLIVION_SERVER_PORT must be a valid port"))
+        })
+    }
+
+    /// Get the public URL oblivion-server is reachable at, used for links in pages and feeds
+    pub fn get_public_url() -> Option<String> {
+        std::env::var("PUBLIC_URL").ok()
+    }
+
+    /// Get how long oblivion-server waits for connections to close on shutdown, in seconds
+    pub fn get_shutdown_grace_secs() -> Option<u64> {
+        Self::parse_optional_u64("SHUTDOWN_GRACE_SECS")
+    }
+
+    /// Get the origins allowed to call oblivion-server from a browser, `*` allows any
+    pub fn get_cors_allowed_origins() -> Vec<String> {
+        std::env::var("CORS_ALLOWED_ORIGINS")
+            .map(|origins| {
+                origins
+                    .split(',')
+                    .map(|origin| origin.trim().to_string())
+                    .filter(|origin| !origin.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get whether to take client IPs from `X-Forwarded-For`, only safe behind a proxy that sets it
+    pub fn get_trust_forwarded_for() -> bool {
+        std::env::var("TRUST_FORWARDED_FOR").is_ok_and(|value| value == "true" || value == "1")
+    }
+
+    /// Get how many proxies in front of oblivion-server append to `X-Forwarded-For`
+    pub fn get_trusted_proxy_hops() -> Option<u64> {
+        Self::parse_optional_u64("TRUSTED_PROXY_HOPS")
+    }
+
+    /// Get how many requests a client IP may make in a burst
+    pub fn get_rate_limit_ip_burst() -> Option<u64> {
+        Self::parse_optional_u64("RATE_LIMIT_IP_BURST")
+    }
+
+    /// Get how many requests a client IP may make per minute (0 disables the limit)
+    pub fn get_rate_limit_ip_per_minute() -> Option<u64> {
+        Self::parse_optional_u64("RATE_LIMIT_IP_PER_MINUTE")
+    }
+
+    /// Get how many requests an API token may make in a burst
+    pub fn get_rate_limit_token_burst() -> Option<u64> {
+        Self::parse_optional_u64("RATE_LIMIT_TOKEN_BURST")
+    }
+
+    /// Get how many requests an API token may make per minute (0 disables the limit)
+    pub fn get_rate_limit_token_per_minute() -> Option<u64> {
+        Self::parse_optional_u64("RATE_LIMIT_TOKEN_PER_MINUTE")
+    }
+
+    /// Get how many history replays may read from the event stream at once
+    pub fn get_max_concurrent_replays() -> Option<u64> {
+        Self::parse_optional_u64("MAX_CONCURRENT_REPLAYS")
+    }
+
+    /// Get the directory cached media is kept in, when not using S3
+    pub fn get_media_dir() -> Option<String> {
+        std::env::var("MEDIA_DIR").ok()
+    }
+
+    /// Get the S3 bucket cached media is kept in, credentials come from the usual `AWS_*` variables
+    pub fn get_media_s3_bucket() -> Option<String> {
+        std::env::var("MEDIA_S3_BUCKET").ok()
+    }
+
+    /// Get the hosts media may be downloaded from
+    pub fn get_media_allowed_hosts() -> Vec<String> {
+        std::env::var("MEDIA_ALLOWED_HOSTS")
+            .map(|hosts| {
+                hosts
+                    .split(',')
+                    .map(|host| host.trim().to_string())
+                    .filter(|host| !host.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the largest media file we'll download, in bytes
+    pub fn get_media_max_bytes() -> Option<u64> {
+        Self::parse_optional_u64("MEDIA_MAX_BYTES")
+    }
+
     /// Get the NATS URL
     pub fn get_nats_url() -> String {
         std::env::var("NATS_URL").unwrap_or_else(|_| "nats://localhost:4222".to_string())
@@ -50,7 +190,7 @@ impl OddbotConfig {
     pub fn parse_optional_u64(env_var: &str) -> Option<u64> {
         std::env::var(env_var).ok().map(|id| {
             id.parse()
-                .expect(format!("{} must be a valid u64", env_var).as_str())
+                .unwrap_or_else(|_| panic!("{} must be a valid u64", env_var))
         })
     }
 }
@@ -4,9 +4,17 @@ use std::env;
 
 /// Create a new database pool
 pub async fn create_db_pool() -> Result<PgPool, OddbotError> {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let database_url = env::var("DATABASE_URL")
+        .map_err(|_| OddbotError::InvalidConfig("DATABASE_URL must be set".into()))?;
 
     PgPool::connect(&database_url)
         .await
         .map_err(OddbotError::Database)
 }
+
+/// Brings the database up to date with the migrations built into the binary
+pub async fn run_migrations(pool: &PgPool) -> Result<(), OddbotError> {
+    sqlx::migrate!().run(pool).await?;
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
 use super::{character::CharacterStore, handler::Handler};
-use crate::prelude::*;
+use crate::{
+    auth::link::LinkCodeStore,
+    prelude::*,
+    skeever::{like::LikeStore, origin::SqueakOriginStore, resqueak::ResqueakStore},
+};
 use serenity::{Client, all::GatewayIntents};
 use sqlx::PgPool;
 use std::{env, sync::Arc};
@@ -17,6 +21,10 @@ impl DiscordBot {
         db_pool: Arc<PgPool>,
         event_stream: Option<Arc<EventStream>>,
         character_store: Arc<CharacterStore>,
+        link_store: Arc<LinkCodeStore>,
+        origin_store: Arc<SqueakOriginStore>,
+        like_store: Arc<LikeStore>,
+        resqueak_store: Arc<ResqueakStore>,
     ) -> Result<Self, OddbotError> {
         // Get our discord token
         let discord_token = env::var("DISCORD_TOKEN").map_err(OddbotError::EnvVar)?;
@@ -26,6 +34,10 @@ impl DiscordBot {
             db_pool.clone(),
             event_stream.clone(),
             character_store.clone(),
+            link_store,
+            origin_store,
+            like_store,
+            resqueak_store,
         );
 
         // Declare our intents for events we're going to listen to
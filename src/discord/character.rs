@@ -1,8 +1,10 @@
 //! A Character is a struct that represents an Oblivion character, which will be tied with a Discord user
 use crate::error::OddbotError;
 use async_nats::jetstream::{self, context::CreateKeyValueError, kv};
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum OblivionError {
@@ -18,6 +20,12 @@ pub enum OblivionError {
     GetCharacter(#[from] kv::EntryError),
     #[error("Failed to delete character")]
     DeleteCharacter(#[from] kv::DeleteError),
+    #[error("Failed to list characters")]
+    ListCharacters(#[from] kv::HistoryError),
+    #[error("Failed to read character keys")]
+    ReadKeys(#[from] kv::WatcherError),
+    #[error("Failed to watch characters")]
+    WatchCharacters(#[source] kv::WatchError),
 }
 
 #[derive(Deserialize, Serialize)]
@@ -28,6 +36,24 @@ pub struct Character {
     pub avatar_url: Option<String>,
 }
 
+/// The public face of a character, leaving out who is behind it on Discord
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct CharacterProfile {
+    pub name: String,
+    pub description: String,
+    pub avatar_url: Option<String>,
+}
+
+impl From<Character> for CharacterProfile {
+    fn from(character: Character) -> Self {
+        Self {
+            name: character.name,
+            description: character.description,
+            avatar_url: character.avatar_url,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct CharacterBuilder {
     discord_id: Option<String>,
@@ -115,6 +141,7 @@ impl Character {
 }
 
 /// A CharacterStore is a struct for storing and retrieving oblivion characters from a key-value store
+#[derive(Debug)]
 pub struct CharacterStore {
     store: kv::Store,
 }
@@ -165,6 +192,31 @@ impl CharacterStore {
         Ok(Some(character))
     }
 
+    /// Gets every character in the store, sorted by name
+    pub async fn list_characters(&self) -> Result<Vec<Character>, OblivionError> {
+        let keys: Vec<String> = self.store.keys().await?.try_collect().await?;
+
+        let mut characters = Vec::with_capacity(keys.len());
+        for key in keys {
+            // Characters deleted since we listed the keys are skipped
+            if let Some(character) = self.get_character(&key).await? {
+                characters.push(character);
+            }
+        }
+        characters.sort_by_key(|character| character.name.to_lowercase());
+
+        Ok(characters)
+    }
+
+    /// Watches every character, keyed by discord id, starting with their current values
+    pub async fn watch(&self) -> Result<kv::Watch, OddbotError> {
+        Ok(self
+            .store
+            .watch_with_history(">")
+            .await
+            .map_err(OblivionError::WatchCharacters)?)
+    }
+
     /// Deletes a character from the store
     pub async fn delete_character(&self, discord_id: &str) -> Result<(), OblivionError> {
         self.store
@@ -33,7 +33,7 @@ pub async fn save_character(
         .description(description.to_string())
         .avatar_url(avatar_url)
         .build()?
-        .save(&store)
+        .save(store)
         .await?;
 
     response
@@ -56,14 +56,14 @@ pub async fn get_character(
     let user_id = interaction.user.id;
 
     // Get the character from the store
-    let character = Character::get_by_discord_id(&user_id.to_string(), &store).await?;
+    let character = Character::get_by_discord_id(&user_id.to_string(), store).await?;
 
     let Some(character) = character else {
         interaction
             .create_response(
                 ctx,
                 CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new().content(format!("I don't know you.")),
+                    CreateInteractionResponseMessage::new().content("I don't know you."),
                 ),
             )
             .await?;
@@ -88,13 +88,13 @@ pub async fn delete_character(
 ) -> Result<(), OddbotError> {
     let user_id = interaction.user.id;
     // Get the character first to save their name for the response
-    let character = Character::get_by_discord_id(&user_id.to_string(), &store).await?;
+    let character = Character::get_by_discord_id(&user_id.to_string(), store).await?;
     let Some(character) = character else {
         interaction
             .create_response(
                 ctx,
                 CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new().content(format!("I don't know you.")),
+                    CreateInteractionResponseMessage::new().content("I don't know you."),
                 ),
             )
             .await?;
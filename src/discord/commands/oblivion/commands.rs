@@ -1,4 +1,4 @@
-use serenity::all::CreateCommand;
+use serenity::all::{CommandOptionType, CommandType, CreateCommand, CreateCommandOption};
 
 pub fn register_character() -> CreateCommand {
     CreateCommand::new("register").description("Register an Oblivion character")
@@ -11,3 +11,37 @@ pub fn get_character() -> CreateCommand {
 pub fn delete_character() -> CreateCommand {
     CreateCommand::new("die").description("Delete your Oblivion character")
 }
+
+pub fn create_token() -> CreateCommand {
+    CreateCommand::new("token")
+        .description("Create an API token for the Oblivion server")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "label", "What the token is for")
+                .required(false),
+        )
+}
+
+pub fn revoke_tokens() -> CreateCommand {
+    CreateCommand::new("revoke-tokens").description("Revoke all of your Oblivion API tokens")
+}
+
+pub fn revoke_token() -> CreateCommand {
+    CreateCommand::new("revoke-token")
+        .description("Revoke one of your Oblivion API tokens, or list them")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "token",
+                "Label or ID of the token to revoke",
+            )
+            .required(false),
+        )
+}
+
+pub fn link_character() -> CreateCommand {
+    CreateCommand::new("link").description("Get a code to link the Oblivion mod to your character")
+}
+
+pub fn resqueak() -> CreateCommand {
+    CreateCommand::new("Resqueak").kind(CommandType::Message)
+}
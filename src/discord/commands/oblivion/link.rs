@@ -0,0 +1,52 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::{
+    auth::link::{LINK_CODE_TTL, LinkCodeStore},
+    discord::character::{Character, CharacterStore},
+    error::OddbotError,
+};
+
+pub async fn create_link_code(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    character_store: &CharacterStore,
+    link_store: &LinkCodeStore,
+) -> Result<(), OddbotError> {
+    let user_id = interaction.user.id.to_string();
+
+    // Codes are tied to a character, so the user needs one first
+    let Some(character) = Character::get_by_discord_id(&user_id, character_store).await? else {
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content("You need to `/register` a character before linking the mod."),
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let link = link_store.create(user_id, character.name).await?;
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(format!(
+                        "Enter this code in the Oblivion mod to link it to **{}**:\n`{}`\n\nIt expires in {} minutes and can only be used once.",
+                        link.character,
+                        link.code,
+                        LINK_CODE_TTL.as_secs() / 60
+                    )),
+            ),
+        )
+        .await?;
+    Ok(())
+}
@@ -1,2 +1,5 @@
 pub mod character;
 pub mod commands;
+pub mod link;
+pub mod resqueak;
+pub mod token;
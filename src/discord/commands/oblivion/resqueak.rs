@@ -0,0 +1,91 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::CreateQuickModal;
+
+use crate::{
+    discord::{character::Character, handler::Handler},
+    error::OddbotError,
+};
+
+/// Longest quote Discord will let someone type into the modal
+const MAX_QUOTE_LENGTH: u16 = 2000;
+
+pub async fn resqueak_message(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    handler: &Handler,
+) -> Result<(), OddbotError> {
+    let Some(ResolvedTarget::Message(message)) = interaction.data.target() else {
+        return Ok(());
+    };
+
+    // Only messages that were published as squeaks can be resqueaked
+    let Some(original) = handler.origin_store.get(&message.id.to_string()).await? else {
+        return respond(ctx, interaction, "Only squeaks can be resqueaked.").await;
+    };
+
+    let user_id = interaction.user.id.to_string();
+    let Some(character) = Character::get_by_discord_id(&user_id, &handler.character_store).await?
+    else {
+        return respond(
+            ctx,
+            interaction,
+            "You need to `/register` a character before resqueaking.",
+        )
+        .await;
+    };
+
+    let modal = CreateQuickModal::new("Resqueak")
+        .timeout(std::time::Duration::from_secs(600))
+        .field(
+            CreateInputText::new(InputTextStyle::Paragraph, "Quote (optional)", "")
+                .max_length(MAX_QUOTE_LENGTH)
+                .required(false),
+        );
+    let Some(response) = interaction.quick_modal(ctx, modal).await? else {
+        return Ok(());
+    };
+    let quote = response.inputs[0].trim();
+    let quote = (!quote.is_empty()).then(|| quote.to_string());
+
+    let avatar_url = character
+        .avatar_url
+        .clone()
+        .unwrap_or_else(|| interaction.user.face());
+    handler
+        .publish_resqueak(&original, &character, avatar_url, quote)
+        .await?;
+
+    response
+        .interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(format!("Resqueaked as **{}**.", character.name)),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Tells only the person who used the command why nothing happened
+async fn respond(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    content: &str,
+) -> Result<(), OddbotError> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::{
+    auth::token::{ApiToken, ApiTokenStore},
+    error::OddbotError,
+};
+
+/// Label used when the user doesn't give their token one
+const DEFAULT_TOKEN_LABEL: &str = "default";
+
+pub async fn create_token(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    store: &ApiTokenStore,
+) -> Result<(), OddbotError> {
+    let user_id = interaction.user.id;
+    let label = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "label")
+        .and_then(|option| option.value.as_str())
+        .unwrap_or(DEFAULT_TOKEN_LABEL);
+
+    let (token, plaintext) = store.issue(user_id.to_string(), label.to_string()).await?;
+
+    // Tokens are only ever shown once, and only to the user who asked for it
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(format!(
+                        "Here is your **{}** token, keep it secret:\n`{}`\n\nYou won't be able to see it again. Use `/revoke-token` if it leaks.",
+                        token.label, plaintext
+                    )),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_tokens(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    store: &ApiTokenStore,
+) -> Result<(), OddbotError> {
+    let user_id = interaction.user.id;
+    let revoked = store.revoke_all(&user_id.to_string()).await?;
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(format!("Revoked {revoked} token(s).")),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_token(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    store: &ApiTokenStore,
+) -> Result<(), OddbotError> {
+    let user_id = interaction.user.id.to_string();
+    let requested = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "token")
+        .and_then(|option| option.value.as_str());
+
+    let tokens = store.list(&user_id).await?;
+    let matching: Vec<_> = tokens
+        .iter()
+        .filter(|token| {
+            requested.is_some_and(|requested| token.id == requested || token.label == requested)
+        })
+        .collect();
+
+    let content = match (requested, matching.as_slice()) {
+        (_, [token]) => match store.revoke(&user_id, &token.id).await? {
+            true => format!("Revoked your **{}** token (`{}`).", token.label, token.id),
+            false => format!("Your **{}** token was already revoked.", token.label),
+        },
+        _ if tokens.is_empty() => "You don't have any active tokens.".to_string(),
+        (Some(requested), []) => format!(
+            "You don't have a token called `{requested}`. Your active tokens are:\n{}",
+            list_tokens(&tokens)
+        ),
+        (Some(_), _) => format!(
+            "More than one token has that label, revoke one by its ID instead:\n{}",
+            list_tokens(&tokens)
+        ),
+        (None, _) => format!(
+            "Your active tokens are:\n{}\n\nRevoke one with `/revoke-token` and its label or ID.",
+            list_tokens(&tokens)
+        ),
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Lists tokens one per line, without ever showing their secrets
+fn list_tokens(tokens: &[ApiToken]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            let character = token
+                .character
+                .as_deref()
+                .map(|character| format!(" for {character}"))
+                .unwrap_or_default();
+            format!("- **{}**{} `{}`", token.label, character, token.id)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
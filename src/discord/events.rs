@@ -2,7 +2,7 @@ use super::handler::Handler;
 use crate::prelude::*;
 use serenity::all::{
     ChannelId, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
-    GuildMemberUpdateEvent, Interaction, MessageId, Reaction,
+    GuildMemberUpdateEvent, Interaction, MessageId, MessageUpdateEvent, Reaction,
 };
 use serenity::async_trait;
 use serenity::model::channel::Message;
@@ -36,6 +36,50 @@ impl EventHandler for Handler {
         }
     }
 
+    /// This event will be dispatched when a message is edited
+    async fn message_update(
+        &self,
+        _ctx: Context,
+        _old_if_available: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            self.handle_oblivion_message_update(&event, oblivion_channel)
+                .await;
+        }
+    }
+
+    /// This event will be dispatched when a message is deleted
+    async fn message_delete(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            self.handle_oblivion_message_delete(channel_id, deleted_message_id, oblivion_channel)
+                .await;
+        }
+    }
+
+    /// This event will be dispatched when messages are deleted in bulk, e.g. by a moderator
+    async fn message_delete_bulk(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        multiple_deleted_messages_ids: Vec<MessageId>,
+        _guild_id: Option<GuildId>,
+    ) {
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            for message_id in multiple_deleted_messages_ids {
+                self.handle_oblivion_message_delete(channel_id, message_id, oblivion_channel)
+                    .await;
+            }
+        }
+    }
+
     /// This event will be dispatched when the bot is ready
     async fn ready(&self, ctx: Context, ready: Ready) {
         tracing::info!("Ready and connected as {}", ready.user.name);
@@ -80,6 +124,53 @@ impl EventHandler for Handler {
                     .unwrap();
                     None
                 }
+                "token" => {
+                    super::commands::oblivion::token::create_token(
+                        &ctx,
+                        &command,
+                        &self.token_store,
+                    )
+                    .await
+                    .unwrap();
+                    None
+                }
+                "revoke-tokens" => {
+                    super::commands::oblivion::token::revoke_tokens(
+                        &ctx,
+                        &command,
+                        &self.token_store,
+                    )
+                    .await
+                    .unwrap();
+                    None
+                }
+                "revoke-token" => {
+                    super::commands::oblivion::token::revoke_token(
+                        &ctx,
+                        &command,
+                        &self.token_store,
+                    )
+                    .await
+                    .unwrap();
+                    None
+                }
+                "link" => {
+                    super::commands::oblivion::link::create_link_code(
+                        &ctx,
+                        &command,
+                        &self.character_store,
+                        &self.link_store,
+                    )
+                    .await
+                    .unwrap();
+                    None
+                }
+                "Resqueak" => {
+                    super::commands::oblivion::resqueak::resqueak_message(&ctx, &command, self)
+                        .await
+                        .unwrap();
+                    None
+                }
                 _ => Some("not implemented :(".to_string()),
             };
 
@@ -101,15 +192,13 @@ impl EventHandler for Handler {
         new: Option<Member>,
         _update: GuildMemberUpdateEvent,
     ) {
-        if let Some(old) = old_if_available {
-            if let Some(new) = new {
-                tracing::debug!(
-                    "Member updated: {} ({:?} -> {:?})",
-                    new.user.name,
-                    old.roles,
-                    new.roles
-                );
-            }
+        if let (Some(old), Some(new)) = (old_if_available, new) {
+            tracing::debug!(
+                "Member updated: {} ({:?} -> {:?})",
+                new.user.name,
+                old.roles,
+                new.roles
+            );
         }
     }
 
@@ -137,11 +226,19 @@ impl EventHandler for Handler {
     /// This event will be dispatched when a reaction is added to a message
     async fn reaction_add(&self, _ctx: Context, reaction: Reaction) {
         tracing::debug!("Reaction added: {:?}", reaction);
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            self.handle_oblivion_reaction(&reaction, oblivion_channel, true)
+                .await;
+        }
     }
 
     /// This event will be dispatched when a reaction is removed from a message
     async fn reaction_remove(&self, _ctx: Context, reaction: Reaction) {
         tracing::debug!("Reaction removed: {:?}", reaction);
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            self.handle_oblivion_reaction(&reaction, oblivion_channel, false)
+                .await;
+        }
     }
 
     /// This event will be dispatched when all reactions are removed from a message
@@ -156,10 +253,23 @@ impl EventHandler for Handler {
             message_id,
             channel_id
         );
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            self.handle_oblivion_reactions_cleared(channel_id, message_id, None, oblivion_channel)
+                .await;
+        }
     }
 
     /// This event will be dispatched when all reactions of a specific emoji are removed from a message
     async fn reaction_remove_emoji(&self, _ctx: Context, reaction: Reaction) {
         tracing::debug!("All {:?} reactions removed from message", reaction.emoji);
+        if let Some(oblivion_channel) = OddbotConfig::get_oblivion_social_channel_id() {
+            self.handle_oblivion_reactions_cleared(
+                reaction.channel_id,
+                reaction.message_id,
+                Some(&reaction.emoji),
+                oblivion_channel,
+            )
+            .await;
+        }
     }
 }
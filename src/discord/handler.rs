@@ -1,20 +1,43 @@
 use crate::prelude::*;
 use crate::{
-    config::OddbotConfig, error::OddbotError, prelude::EventStream, skeever::squeak::Squeak,
+    auth::{link::LinkCodeStore, token::ApiTokenStore},
+    config::OddbotConfig,
+    error::OddbotError,
+    prelude::EventStream,
+    skeever::{
+        like::{LikeStore, SqueakLike, SqueakUnlike},
+        origin::{SqueakOrigin, SqueakOriginStore},
+        resqueak::ResqueakStore,
+        revision::{SqueakDelete, SqueakEdit},
+        squeak::{Squeak, SqueakSource},
+    },
+};
+use serenity::all::{
+    ChannelId, Context, GuildId, Member, Message, MessageId, MessageUpdateEvent, Reaction,
+    ReactionType, Timestamp,
 };
-use serenity::all::{Context, GuildId, Member, Message};
 use sqlx::{PgPool, types::time::OffsetDateTime};
 use std::sync::Arc;
 
-use super::character::CharacterStore;
+use super::character::{Character, CharacterStore};
 use super::commands::oblivion;
 
+/// The emoji that likes a squeak unless one is configured
+const DEFAULT_LIKE_EMOJI: &str = "👍";
+/// The emoji that resqueaks a squeak unless one is configured
+const DEFAULT_RESQUEAK_EMOJI: &str = "🔁";
+
 /// Default handler for the Discord bot
 pub struct Handler {
     pub guild_id: Option<GuildId>,
     pub db_pool: Arc<PgPool>,
     pub event_stream: Option<Arc<EventStream>>,
     pub character_store: Arc<CharacterStore>,
+    pub token_store: Arc<ApiTokenStore>,
+    pub link_store: Arc<LinkCodeStore>,
+    pub origin_store: Arc<SqueakOriginStore>,
+    pub like_store: Arc<LikeStore>,
+    pub resqueak_store: Arc<ResqueakStore>,
 }
 
 impl Handler {
@@ -23,21 +46,26 @@ impl Handler {
         db_pool: Arc<PgPool>,
         event_stream: Option<Arc<EventStream>>,
         character_store: Arc<CharacterStore>,
+        link_store: Arc<LinkCodeStore>,
+        origin_store: Arc<SqueakOriginStore>,
+        like_store: Arc<LikeStore>,
+        resqueak_store: Arc<ResqueakStore>,
     ) -> Self {
-        let guild_id = {
-            // Check if we're configured to run against a specific guild
-            let guild_id = OddbotConfig::get_guild_id();
-            match guild_id {
-                Some(id) => Some(GuildId::new(id)),
-                None => None,
-            }
-        };
+        // Check if we're configured to run against a specific guild
+        let guild_id = OddbotConfig::get_guild_id().map(GuildId::new);
+
+        let token_store = Arc::new(ApiTokenStore::new(db_pool.clone()));
 
         Self {
             guild_id,
             db_pool,
             event_stream,
             character_store,
+            token_store,
+            link_store,
+            origin_store,
+            like_store,
+            resqueak_store,
         }
     }
 
@@ -53,6 +81,11 @@ impl Handler {
             oblivion::commands::register_character(),
             oblivion::commands::get_character(),
             oblivion::commands::delete_character(),
+            oblivion::commands::create_token(),
+            oblivion::commands::revoke_tokens(),
+            oblivion::commands::revoke_token(),
+            oblivion::commands::link_character(),
+            oblivion::commands::resqueak(),
         ];
         let commands = guild_id.set_commands(&ctx.http, commands).await;
         tracing::debug!("Registered guild slash commands: {commands:?}");
@@ -224,7 +257,22 @@ impl Handler {
         };
 
         // Build the squeak out of the message
-        let mut squeak_builder = Squeak::builder().content(message).user(character.name);
+        let mut squeak_builder = Squeak::builder()
+            .content(message)
+            .user(character.name.clone())
+            .character(character.name)
+            .source(SqueakSource::Discord)
+            .discord_message(msg.channel_id.to_string(), msg.id.to_string());
+
+        // Replies to another squeak continue its conversation
+        if let Some(parent) = self.find_parent_squeak(msg).await {
+            squeak_builder = squeak_builder.reply_to(parent.squeak_id, parent.thread_root());
+        }
+
+        // Use Discord's timestamp so the squeak shows when it was actually written
+        if let Some(created_at) = to_offset_date_time(msg.timestamp) {
+            squeak_builder = squeak_builder.created_at(created_at);
+        }
 
         // Append the avatar URL if available
         if let Some(avatar_url) = msg.author.avatar_url() {
@@ -235,10 +283,480 @@ impl Handler {
         let squeak = squeak_builder.await.map_err(OddbotError::SqueakPublish)?;
 
         // Convert the squeak into an Event Stream message
-        let message = EventMessage::from(squeak);
+        let message = EventMessage::from(squeak.clone());
 
         tracing::debug!("Publishing squeak {} to event stream", message.payload.id);
         // Publish the message to the event stream
-        Ok(event_stream.publish(message).await?)
+        event_stream.publish(message).await?;
+
+        // Remember which squeak this message became, so edits and deletions can follow it, but
+        // only once it exists for them to apply to
+        if let Err(err) = self.origin_store.record(&msg.id.to_string(), &squeak).await {
+            tracing::error!("Failed to record origin of squeak {}: {}", squeak.id, err);
+        }
+
+        Ok(())
+    }
+
+    /// Finds the squeak a message replies to, if it replies to one in the same channel
+    async fn find_parent_squeak(&self, msg: &Message) -> Option<SqueakOrigin> {
+        let reference = msg.message_reference.as_ref()?;
+        if reference.channel_id != msg.channel_id {
+            return None;
+        }
+        let parent_message_id = reference.message_id?.to_string();
+
+        match self.origin_store.get(&parent_message_id).await {
+            Ok(origin) => origin,
+            Err(err) => {
+                tracing::error!(
+                    "Failed to look up replied-to message {}: {}",
+                    parent_message_id,
+                    err
+                );
+                None
+            }
+        }
     }
+
+    /// Sends an edit of an oblivion message to the event stream
+    pub async fn handle_oblivion_message_update(
+        &self,
+        event: &MessageUpdateEvent,
+        channel_id: u64,
+    ) {
+        if event.channel_id.get() != channel_id {
+            return;
+        }
+
+        // Discord also sends updates when embeds resolve, those leave the content alone
+        let Some(content) = &event.content else {
+            return;
+        };
+
+        if let Err(err) = self
+            .publish_message_edit(event.id, content.clone(), event.edited_timestamp)
+            .await
+        {
+            tracing::error!("Failed to publish squeak edit: {}", err);
+        }
+    }
+
+    /// Publishes an edit of a message that was published as a squeak
+    pub async fn publish_message_edit(
+        &self,
+        message_id: MessageId,
+        content: String,
+        edited_at: Option<Timestamp>,
+    ) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let Some(origin) = self.origin_store.get(&message_id.to_string()).await? else {
+            tracing::debug!("Message {} was never a squeak, ignoring edit", message_id);
+            return Ok(());
+        };
+
+        let mut edit = SqueakEdit::new(origin.squeak_id, origin.author, content);
+        if let Some(edited_at) = edited_at.and_then(to_offset_date_time) {
+            edit = edit.edited_at(edited_at);
+        }
+
+        tracing::debug!(
+            "Publishing edit of squeak {} to event stream",
+            edit.squeak_id
+        );
+        event_stream.publish(EventMessage::from(edit)).await
+    }
+
+    /// Sends the deletion of an oblivion message to the event stream
+    pub async fn handle_oblivion_message_delete(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        oblivion_channel_id: u64,
+    ) {
+        if channel_id.get() != oblivion_channel_id {
+            return;
+        }
+
+        if let Err(err) = self.publish_message_delete(message_id).await {
+            tracing::error!("Failed to publish squeak deletion: {}", err);
+        }
+    }
+
+    /// Sends a like or resqueak of an oblivion message to the event stream, or takes it back
+    pub async fn handle_oblivion_reaction(
+        &self,
+        reaction: &Reaction,
+        channel_id: u64,
+        added: bool,
+    ) {
+        if reaction.channel_id.get() != channel_id {
+            return;
+        }
+
+        if is_like_emoji(&reaction.emoji) {
+            if let Err(err) = self.publish_like(reaction, added).await {
+                tracing::error!("Failed to publish squeak like: {}", err);
+            }
+        } else if is_resqueak_emoji(&reaction.emoji) {
+            let result = match added {
+                true => self.publish_reaction_resqueak(reaction).await,
+                false => self.retract_reaction_resqueak(reaction).await,
+            };
+            if let Err(err) = result {
+                tracing::error!("Failed to publish resqueak: {}", err);
+            }
+        }
+    }
+
+    /// Records a reactor's character liking or unliking a squeak, publishing it if that changed anything
+    pub async fn publish_like(&self, reaction: &Reaction, liked: bool) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let Some(user_id) = reaction.user_id else {
+            return Ok(());
+        };
+
+        let message_id = reaction.message_id.to_string();
+        let Some(origin) = self.origin_store.get(&message_id).await? else {
+            tracing::debug!(
+                "Message {} was never a squeak, ignoring reaction",
+                message_id
+            );
+            return Ok(());
+        };
+
+        // Likes come from characters, just like squeaks do
+        let Some(character) = self
+            .character_store
+            .get_character(&user_id.to_string())
+            .await?
+        else {
+            tracing::debug!("User {} does not have a character, ignoring like", user_id);
+            return Ok(());
+        };
+
+        let (squeak_id, author) = (origin.squeak_id, origin.author);
+        match liked {
+            true => {
+                let Some(likes) = self.like_store.like(squeak_id, &character.name).await? else {
+                    return Ok(());
+                };
+                tracing::debug!("Publishing like of squeak {} to event stream", squeak_id);
+                let like = SqueakLike::new(squeak_id, author, character.name, likes);
+                event_stream.publish(EventMessage::from(like)).await
+            }
+            false => {
+                let Some(likes) = self.like_store.unlike(squeak_id, &character.name).await? else {
+                    return Ok(());
+                };
+                tracing::debug!("Publishing unlike of squeak {} to event stream", squeak_id);
+                let unlike = SqueakUnlike::new(squeak_id, author, character.name, likes);
+                event_stream.publish(EventMessage::from(unlike)).await
+            }
+        }
+    }
+
+    /// Takes back every like and resqueak of an oblivion message once its like or resqueak
+    /// reactions are all removed
+    pub async fn handle_oblivion_reactions_cleared(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        emoji: Option<&ReactionType>,
+        oblivion_channel: u64,
+    ) {
+        if channel_id.get() != oblivion_channel {
+            return;
+        }
+
+        // Without an emoji every reaction was removed
+        let likes = match emoji.is_none_or(is_like_emoji) {
+            true => self.publish_likes_cleared(message_id).await,
+            false => Ok(()),
+        };
+        if let Err(err) = likes {
+            tracing::error!("Failed to publish cleared squeak likes: {}", err);
+        }
+
+        let resqueaks = match emoji.is_none_or(is_resqueak_emoji) {
+            true => self.publish_resqueaks_cleared(message_id).await,
+            false => Ok(()),
+        };
+        if let Err(err) = resqueaks {
+            tracing::error!("Failed to publish cleared resqueaks: {}", err);
+        }
+    }
+
+    /// Clears a squeak's likes, publishing an unlike for every character who liked it
+    pub async fn publish_likes_cleared(&self, message_id: MessageId) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let message_id = message_id.to_string();
+        let Some(origin) = self.origin_store.get(&message_id).await? else {
+            tracing::debug!(
+                "Message {} was never a squeak, ignoring cleared reactions",
+                message_id
+            );
+            return Ok(());
+        };
+
+        let characters = self.like_store.clear(origin.squeak_id).await?;
+        tracing::debug!(
+            "Publishing {} unlikes of squeak {} to event stream",
+            characters.len(),
+            origin.squeak_id
+        );
+        // Counting down, so the last unlike leaves the squeak with none
+        let mut likes = characters.len() as u64;
+        for character in characters {
+            likes -= 1;
+            let unlike =
+                SqueakUnlike::new(origin.squeak_id, origin.author.clone(), character, likes);
+            event_stream.publish(EventMessage::from(unlike)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes back every resqueak of a squeak made by reacting, publishing a deletion for each
+    pub async fn publish_resqueaks_cleared(
+        &self,
+        message_id: MessageId,
+    ) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let message_id = message_id.to_string();
+        let Some(original) = self.origin_store.get(&message_id).await? else {
+            return Ok(());
+        };
+
+        let resqueaks = self.resqueak_store.take_all(original.squeak_id).await?;
+        tracing::debug!(
+            "Publishing deletion of {} resqueaks of squeak {} to event stream",
+            resqueaks.len(),
+            original.squeak_id
+        );
+        for resqueak in resqueaks {
+            let delete = SqueakDelete::new(resqueak.squeak_id, resqueak.author);
+            event_stream.publish(EventMessage::from(delete)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Publishes a character's resqueak of a squeak, quoting it when there's a quote
+    pub async fn publish_resqueak(
+        &self,
+        original: &SqueakOrigin,
+        character: &Character,
+        avatar_url: String,
+        quote: Option<String>,
+    ) -> Result<Squeak, OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let resqueak = build_resqueak(original, character, avatar_url, quote).await?;
+        tracing::debug!(
+            "Publishing resqueak of squeak {} to event stream",
+            original.squeak_id
+        );
+        event_stream
+            .publish(EventMessage::from(resqueak.clone()))
+            .await?;
+
+        Ok(resqueak)
+    }
+
+    /// Resqueaks a squeak for the reactor's character, unless they already resqueaked it by reacting
+    pub async fn publish_reaction_resqueak(&self, reaction: &Reaction) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let Some(user_id) = reaction.user_id else {
+            return Ok(());
+        };
+        let user_id = user_id.to_string();
+
+        let message_id = reaction.message_id.to_string();
+        let Some(original) = self.origin_store.get(&message_id).await? else {
+            tracing::debug!(
+                "Message {} was never a squeak, ignoring reaction",
+                message_id
+            );
+            return Ok(());
+        };
+
+        let Some(character) = self.character_store.get_character(&user_id).await? else {
+            tracing::debug!(
+                "User {} does not have a character, ignoring resqueak",
+                user_id
+            );
+            return Ok(());
+        };
+
+        let avatar_url = character
+            .avatar_url
+            .clone()
+            .or_else(|| reaction.member.as_ref().map(|member| member.user.face()));
+        let Some(avatar_url) = avatar_url else {
+            tracing::debug!("User {} has no avatar, ignoring resqueak", user_id);
+            return Ok(());
+        };
+
+        let resqueak = build_resqueak(&original, &character, avatar_url, None).await?;
+        if !self
+            .resqueak_store
+            .record(original.squeak_id, &user_id, &resqueak)
+            .await?
+        {
+            return Ok(());
+        }
+
+        tracing::debug!(
+            "Publishing resqueak of squeak {} to event stream",
+            original.squeak_id
+        );
+        if let Err(err) = event_stream.publish(EventMessage::from(resqueak)).await {
+            // Forget the resqueak so reacting again can retry it
+            if let Err(err) = self.resqueak_store.take(original.squeak_id, &user_id).await {
+                tracing::error!("Failed to forget unpublished resqueak: {}", err);
+            }
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the resqueak a reactor made by reacting, once they remove the reaction
+    pub async fn retract_reaction_resqueak(&self, reaction: &Reaction) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let Some(user_id) = reaction.user_id else {
+            return Ok(());
+        };
+
+        let message_id = reaction.message_id.to_string();
+        let Some(original) = self.origin_store.get(&message_id).await? else {
+            return Ok(());
+        };
+
+        let Some(resqueak) = self
+            .resqueak_store
+            .take(original.squeak_id, &user_id.to_string())
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let delete = SqueakDelete::new(resqueak.squeak_id, resqueak.author);
+        tracing::debug!(
+            "Publishing deletion of resqueak {} to event stream",
+            delete.squeak_id
+        );
+        event_stream.publish(EventMessage::from(delete)).await
+    }
+
+    /// Publishes a tombstone for a message that was published as a squeak
+    pub async fn publish_message_delete(&self, message_id: MessageId) -> Result<(), OddbotError> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(OddbotError::InvalidConfig(
+                "Event stream not initialized".to_string(),
+            ));
+        };
+
+        let message_id = message_id.to_string();
+        let Some(origin) = self.origin_store.get(&message_id).await? else {
+            tracing::debug!(
+                "Message {} was never a squeak, ignoring deletion",
+                message_id
+            );
+            return Ok(());
+        };
+
+        let delete = SqueakDelete::new(origin.squeak_id, origin.author);
+        tracing::debug!(
+            "Publishing deletion of squeak {} to event stream",
+            delete.squeak_id
+        );
+        event_stream.publish(EventMessage::from(delete)).await?;
+
+        self.origin_store.remove(&message_id).await
+    }
+}
+
+/// Checks whether a reaction uses the configured like emoji
+fn is_like_emoji(emoji: &ReactionType) -> bool {
+    let like_emoji =
+        OddbotConfig::get_like_emoji().unwrap_or_else(|| DEFAULT_LIKE_EMOJI.to_string());
+    is_emoji(emoji, &like_emoji)
+}
+
+/// Checks whether a reaction uses the configured resqueak emoji
+fn is_resqueak_emoji(emoji: &ReactionType) -> bool {
+    let resqueak_emoji =
+        OddbotConfig::get_resqueak_emoji().unwrap_or_else(|| DEFAULT_RESQUEAK_EMOJI.to_string());
+    is_emoji(emoji, &resqueak_emoji)
+}
+
+/// Checks a reaction against a unicode emoji, or a custom emoji's name or ID
+fn is_emoji(emoji: &ReactionType, expected: &str) -> bool {
+    match emoji {
+        ReactionType::Unicode(emoji) => emoji == expected,
+        ReactionType::Custom { id, name, .. } => {
+            name.as_deref() == Some(expected) || id.to_string() == expected
+        }
+        _ => false,
+    }
+}
+
+/// Builds a character's resqueak of a squeak, with an empty quote for a plain resqueak
+async fn build_resqueak(
+    original: &SqueakOrigin,
+    character: &Character,
+    avatar_url: String,
+    quote: Option<String>,
+) -> Result<Squeak, OddbotError> {
+    Squeak::builder()
+        .content(quote.unwrap_or_default())
+        .user(character.name.clone())
+        .character(character.name.clone())
+        .avatar(avatar_url)
+        .source(SqueakSource::Discord)
+        .resqueak(original.squeak_id)
+        .await
+        .map_err(OddbotError::SqueakPublish)
+}
+
+/// Converts a Discord timestamp, keeping millisecond precision
+fn to_offset_date_time(timestamp: Timestamp) -> Option<OffsetDateTime> {
+    let nanos = i128::from(timestamp.timestamp_millis()) * 1_000_000;
+    OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
 }
@@ -1,9 +1,14 @@
-use crate::{discord, skeever::squeak::SqueakError};
+use crate::{
+    auth::link::LinkError,
+    discord,
+    feed::FeedError,
+    skeever::{like::LikeError, origin::OriginError, resqueak::ResqueakError, squeak::SqueakError},
+};
 use async_nats::{
     ConnectErrorKind,
     jetstream::{
         consumer::pull::BatchErrorKind,
-        context::{CreateStreamError, DeleteStreamError, PublishError},
+        context::{CreateStreamError, DeleteStreamError, GetStreamError, PublishError},
         stream::ConsumerError,
     },
 };
@@ -13,6 +18,8 @@ use thiserror::Error;
 pub enum OddbotError {
     #[error("Database error")]
     Database(#[from] sqlx::Error),
+    #[error("Database migration error")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
     #[error("Serialization error")]
     SerdeError(#[from] serde_json::Error),
     #[error("Error creating stream")]
@@ -25,18 +32,40 @@ pub enum OddbotError {
     NatsConnect(#[from] async_nats::error::Error<ConnectErrorKind>),
     #[error("Error batching nats messages")]
     NatsBatch(#[from] async_nats::error::Error<BatchErrorKind>),
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
     #[error("Environment variable error")]
     EnvVar(#[from] std::env::VarError),
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
     #[error("Squeak publish error")]
     SqueakPublish(SqueakError),
+    #[error("Error getting stream")]
+    StreamGet(GetStreamError),
+    #[error("Error reading stored event: {0}")]
+    StreamRead(String),
     #[error("Error creating consumer")]
     StreamConsumerCreate(#[from] ConsumerError),
     #[error("Error with Oblivion functionality")]
     OblivionError(#[from] discord::character::OblivionError),
+    #[error("Error with feed frames")]
+    Feed(#[from] FeedError),
+    #[error("Error with likes")]
+    Like(#[from] LikeError),
+    #[error("Error with squeak origins")]
+    Origin(#[from] OriginError),
+    #[error("Error with resqueaks")]
+    Resqueak(#[from] ResqueakError),
+    #[error("Error with link codes")]
+    Link(#[from] LinkError),
     #[error("Error with serenity functionality")]
-    Serenity(#[from] serenity::Error),
+    Serenity(#[from] Box<serenity::Error>),
     #[error("Error sending websockets message to client")]
     WebsocketSend(String),
 }
+
+impl From<serenity::Error> for OddbotError {
+    fn from(err: serenity::Error) -> Self {
+        OddbotError::Serenity(Box::new(err))
+    }
+}
@@ -1,7 +1,16 @@
-use crate::skeever::squeak::Squeak;
+use crate::{
+    game::event::GameEvent,
+    skeever::{
+        like::{SqueakLike, SqueakUnlike},
+        revision::{SqueakDelete, SqueakEdit},
+        squeak::Squeak,
+    },
+};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Deserialize, Serialize)]
+/// An event as published to the stream, with the subject it was published on
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct EventMessage<T>
 where
     T: Serialize,
@@ -19,3 +28,49 @@ impl From<Squeak> for EventMessage<Squeak> {
         }
     }
 }
+
+impl From<GameEvent> for EventMessage<GameEvent> {
+    fn from(event: GameEvent) -> Self {
+        let subject = event.get_subject();
+        EventMessage {
+            subject,
+            payload: event,
+        }
+    }
+}
+
+impl From<SqueakEdit> for EventMessage<SqueakEdit> {
+    fn from(edit: SqueakEdit) -> Self {
+        EventMessage {
+            subject: SqueakEdit::get_subject(),
+            payload: edit,
+        }
+    }
+}
+
+impl From<SqueakDelete> for EventMessage<SqueakDelete> {
+    fn from(delete: SqueakDelete) -> Self {
+        EventMessage {
+            subject: SqueakDelete::get_subject(),
+            payload: delete,
+        }
+    }
+}
+
+impl From<SqueakLike> for EventMessage<SqueakLike> {
+    fn from(like: SqueakLike) -> Self {
+        EventMessage {
+            subject: SqueakLike::get_subject(),
+            payload: like,
+        }
+    }
+}
+
+impl From<SqueakUnlike> for EventMessage<SqueakUnlike> {
+    fn from(unlike: SqueakUnlike) -> Self {
+        EventMessage {
+            subject: SqueakUnlike::get_subject(),
+            payload: unlike,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// Where to resume reading the event stream from
+#[derive(Clone, Copy, Debug)]
+pub enum EventCursor {
+    /// Resume after a stream sequence number
+    Sequence(u64),
+    /// Resume after the event with this ULID
+    Id(ulid::Ulid),
+}
+
+impl FromStr for EventCursor {
+    type Err = ulid::DecodeError;
+
+    /// Parses a cursor, preferring sequence numbers and falling back to ULIDs
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(sequence) = value.parse() {
+            return Ok(EventCursor::Sequence(sequence));
+        }
+
+        ulid::Ulid::from_string(value).map(EventCursor::Id)
+    }
+}
+
+impl fmt::Display for EventCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventCursor::Sequence(sequence) => write!(f, "{}", sequence),
+            EventCursor::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+/// When a ULID was created, or `None` if that lies past what `OffsetDateTime` can hold
+pub fn ulid_created_at(id: ulid::Ulid) -> Option<OffsetDateTime> {
+    let nanos = i128::from(id.timestamp_ms()) * 1_000_000;
+    OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+}
+
+/// A query for a page of stored events, oldest first
+#[derive(Clone, Debug)]
+pub struct EventQuery {
+    /// Only return events after this cursor
+    pub after: Option<EventCursor>,
+    /// Only return events published at or after this time
+    pub since: Option<OffsetDateTime>,
+    /// Only return events published before this time
+    pub until: Option<OffsetDateTime>,
+    /// Subject filters, an empty list matches every subject on the stream
+    pub subjects: Vec<String>,
+    /// Maximum number of events to return
+    pub limit: usize,
+}
+
+impl Default for EventQuery {
+    fn default() -> Self {
+        Self {
+            after: None,
+            since: None,
+            until: None,
+            subjects: Vec::new(),
+            limit: 50,
+        }
+    }
+}
+
+impl EventQuery {
+    /// Checks whether an event on a subject is one the query's subject filters allow
+    pub fn allows_subject(&self, subject: &str) -> bool {
+        self.subjects.is_empty()
+            || self
+                .subjects
+                .iter()
+                .any(|filter| subject_matches(filter, subject))
+    }
+}
+
+/// Checks a subject against a NATS subject filter, where `*` matches one token and `>` the rest
+pub fn subject_matches(filter: &str, subject: &str) -> bool {
+    let mut subject = subject.split('.');
+    for token in filter.split('.') {
+        match (token, subject.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (token, Some(part)) if token == part => {}
+            _ => return false,
+        }
+    }
+    subject.next().is_none()
+}
+
+/// An event read back from the stream
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct StoredEvent {
+    pub sequence: u64,
+    pub subject: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub published: OffsetDateTime,
+    /// A `Squeak` or `GameEvent`, depending on the subject
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+}
+
+/// A page of stored events with the cursor for the next page, if there is one
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct EventPage {
+    pub events: Vec<StoredEvent>,
+    pub next: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subject_filters_match_nats_wildcards() {
+        assert!(subject_matches("a.b.c", "a.b.c"));
+        assert!(!subject_matches("a.b.c", "a.b"));
+        assert!(!subject_matches("a.b", "a.b.c"));
+        assert!(subject_matches("a.*.c", "a.b.c"));
+        assert!(!subject_matches("a.*", "a.b.c"));
+        assert!(subject_matches("a.>", "a.b.c"));
+        assert!(!subject_matches("a.>", "a"));
+        assert!(!subject_matches("a.b.>", "a.c.d"));
+    }
+
+    #[test]
+    fn queries_without_subjects_allow_everything() {
+        assert!(EventQuery::default().allows_subject("anything.at.all"));
+
+        let query = EventQuery {
+            subjects: vec!["a.>".to_string(), "b.c".to_string()],
+            ..Default::default()
+        };
+        assert!(query.allows_subject("a.b"));
+        assert!(query.allows_subject("b.c"));
+        assert!(!query.allows_subject("b.d"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{event_stream::query::ulid_created_at, prelude::*};
 use async_nats::{
     Client as NatsClient,
     jetstream::{
@@ -6,9 +6,14 @@ use async_nats::{
         consumer::{Consumer, pull},
     },
 };
+use futures::StreamExt;
 use serde::Serialize;
 use std::time::Duration;
 
+/// How long an ephemeral query consumer may sit idle before NATS cleans it up
+const QUERY_CONSUMER_INACTIVE_THRESHOLD: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
 pub struct EventStream {
     stream_name: String,
     jetstream: Jetstream,
@@ -66,15 +71,39 @@ impl EventStream {
         Ok(())
     }
 
+    /// Checks that NATS is reachable and the stream exists
+    pub async fn check(&self) -> Result<(), OddbotError> {
+        self.jetstream
+            .get_stream(&self.stream_name)
+            .await
+            .map_err(OddbotError::StreamGet)?;
+
+        Ok(())
+    }
+
+    /// Gets the sequence number of the newest message on the stream, 0 when it's empty
+    pub async fn last_sequence(&self) -> Result<u64, OddbotError> {
+        let stream = self
+            .jetstream
+            .get_stream(&self.stream_name)
+            .await
+            .map_err(OddbotError::StreamGet)?;
+
+        Ok(stream.cached_info().state.last_sequence)
+    }
+
     /// Publish an event to the stream
     pub async fn publish<T>(&self, message: EventMessage<T>) -> Result<(), OddbotError>
     where
         T: Serialize,
     {
         let data = serde_json::to_vec(&message.payload).map_err(OddbotError::SerdeError)?;
+        // Wait for the stream to acknowledge the message, so callers know it was stored
         self.jetstream
             .publish(message.subject, data.into())
             .await
+            .map_err(OddbotError::StreamPublish)?
+            .await
             .map_err(OddbotError::StreamPublish)?;
 
         Ok(())
@@ -84,12 +113,12 @@ impl EventStream {
     pub async fn create_consumer(
         &self,
         name: Option<String>,
-        filter: String,
+        filters: Vec<String>,
         deliver_policy: Option<jetstream::consumer::DeliverPolicy>,
     ) -> Result<Consumer<pull::Config>, OddbotError> {
         let config = jetstream::consumer::pull::Config {
             durable_name: name,
-            filter_subject: filter,
+            filter_subjects: filters,
             max_deliver: 3,
             // Add delivery policy for historical messages
             deliver_policy: deliver_policy.unwrap_or(jetstream::consumer::DeliverPolicy::New),
@@ -101,4 +130,213 @@ impl EventStream {
             .await
             .map_err(OddbotError::StreamConsumerCreate)
     }
+
+    /// Reads a page of stored events, oldest first
+    pub async fn query(&self, query: &EventQuery) -> Result<EventPage, OddbotError> {
+        let stream = self
+            .jetstream
+            .get_stream(&self.stream_name)
+            .await
+            .map_err(OddbotError::StreamGet)?;
+
+        // Ephemeral consumer that only lives as long as this query
+        let consumer = stream
+            .create_consumer(jetstream::consumer::pull::Config {
+                filter_subjects: query.subjects.clone(),
+                deliver_policy: Self::query_deliver_policy(query),
+                ack_policy: jetstream::consumer::AckPolicy::None,
+                inactive_threshold: QUERY_CONSUMER_INACTIVE_THRESHOLD,
+                ..Default::default()
+            })
+            .await?;
+        let consumer_name = consumer.cached_info().name.clone();
+
+        let page = Self::read_page(&consumer, query).await;
+
+        if let Err(e) = stream.delete_consumer(&consumer_name).await {
+            tracing::warn!("Failed to delete query consumer {}: {}", consumer_name, e);
+        }
+
+        page
+    }
+
+    /// Works out where a query consumer should start delivering from
+    fn query_deliver_policy(query: &EventQuery) -> jetstream::consumer::DeliverPolicy {
+        use jetstream::consumer::DeliverPolicy;
+
+        // ULIDs carry their creation time, so we can seek close to them and skip the rest
+        let start_time = match query.after {
+            Some(EventCursor::Sequence(sequence)) => {
+                return DeliverPolicy::ByStartSequence {
+                    start_sequence: sequence.saturating_add(1),
+                };
+            }
+            // Cursors are checked when they're parsed, but nothing can come after a ULID past year 9999
+            Some(EventCursor::Id(id)) => match ulid_created_at(id) {
+                Some(created) => Some(query.since.map_or(created, |since| since.max(created))),
+                None => return DeliverPolicy::New,
+            },
+            None => query.since,
+        };
+
+        match start_time {
+            Some(start_time) => DeliverPolicy::ByStartTime { start_time },
+            None => DeliverPolicy::All,
+        }
+    }
+
+    /// Fetches messages from a query consumer until the page is full or the stream runs dry
+    async fn read_page(
+        consumer: &Consumer<pull::Config>,
+        query: &EventQuery,
+    ) -> Result<EventPage, OddbotError> {
+        let after_id = match query.after {
+            Some(EventCursor::Id(id)) => Some(id),
+            _ => None,
+        };
+        let mut events = Vec::with_capacity(query.limit);
+
+        'fetch: while events.len() < query.limit {
+            let mut messages = consumer
+                .fetch()
+                .max_messages(query.limit - events.len())
+                .messages()
+                .await?;
+
+            let mut fetched = 0;
+            while let Some(message) = messages.next().await {
+                let message = message.map_err(|e| OddbotError::StreamRead(e.to_string()))?;
+                let info = message
+                    .info()
+                    .map_err(|e| OddbotError::StreamRead(e.to_string()))?;
+                fetched += 1;
+
+                if query.until.is_some_and(|until| info.published >= until) {
+                    break 'fetch;
+                }
+                // Sequence cursors can't seek by time, so older events are skipped here instead
+                if query.since.is_some_and(|since| info.published < since) {
+                    continue;
+                }
+
+                let payload: serde_json::Value = serde_json::from_slice(&message.payload)?;
+
+                // Skip anything at or before the ULID cursor
+                if let Some(after_id) = after_id {
+                    let id = payload
+                        .get("id")
+                        .and_then(|id| id.as_str())
+                        .and_then(|id| ulid::Ulid::from_string(id).ok());
+                    if id.is_none_or(|id| id <= after_id) {
+                        continue;
+                    }
+                }
+
+                events.push(StoredEvent {
+                    sequence: info.stream_sequence,
+                    subject: message.subject.to_string(),
+                    published: info.published,
+                    payload,
+                });
+            }
+
+            if fetched == 0 {
+                break;
+            }
+        }
+
+        // A full page means there may be more to read
+        let next = match events.last() {
+            Some(last) if events.len() >= query.limit => {
+                Some(EventCursor::Sequence(last.sequence).to_string())
+            }
+            _ => None,
+        };
+
+        Ok(EventPage { events, next })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jetstream::consumer::DeliverPolicy;
+    use time::OffsetDateTime;
+
+    fn at(unix_timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(unix_timestamp).unwrap()
+    }
+
+    fn query(after: Option<EventCursor>, since: Option<OffsetDateTime>) -> EventQuery {
+        EventQuery {
+            after,
+            since,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delivers_everything_without_a_cursor() {
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(None, None)),
+            DeliverPolicy::All
+        );
+    }
+
+    #[test]
+    fn starts_at_since_without_a_cursor() {
+        let since = at(1_735_689_600);
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(None, Some(since))),
+            DeliverPolicy::ByStartTime { start_time: since }
+        );
+    }
+
+    #[test]
+    fn starts_after_a_sequence_cursor() {
+        let after = Some(EventCursor::Sequence(41));
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(after, None)),
+            DeliverPolicy::ByStartSequence { start_sequence: 42 }
+        );
+    }
+
+    #[test]
+    fn saturates_the_largest_sequence_cursor() {
+        let after = Some(EventCursor::Sequence(u64::MAX));
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(after, None)),
+            DeliverPolicy::ByStartSequence {
+                start_sequence: u64::MAX
+            }
+        );
+    }
+
+    #[test]
+    fn seeks_to_the_later_of_since_and_a_ulid_cursor() {
+        let created = at(1_748_779_200);
+        let id = ulid::Ulid::from_parts((created.unix_timestamp_nanos() / 1_000_000) as u64, 0);
+        let earlier = at(1_735_689_600);
+        let later = at(1_764_547_200);
+
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(Some(EventCursor::Id(id)), Some(earlier))),
+            DeliverPolicy::ByStartTime {
+                start_time: created
+            }
+        );
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(Some(EventCursor::Id(id)), Some(later))),
+            DeliverPolicy::ByStartTime { start_time: later }
+        );
+    }
+
+    #[test]
+    fn delivers_nothing_after_a_ulid_past_year_9999() {
+        let id = ulid::Ulid::from_string("7ZZZZZZZZZZZZZZZZZZZZZZZZZ").unwrap();
+        assert_eq!(
+            EventStream::query_deliver_policy(&query(Some(EventCursor::Id(id)), None)),
+            DeliverPolicy::New
+        );
+    }
 }
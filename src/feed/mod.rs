@@ -0,0 +1,253 @@
+//! The feed is everything oblivion-server pushes out to live clients
+use crate::{
+    event_stream::query::StoredEvent,
+    game::event::GameEvent,
+    skeever::{
+        like::{SqueakLike, SqueakUnlike},
+        revision::{SqueakDelete, SqueakEdit},
+        squeak::Squeak,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+pub mod protocol;
+
+#[derive(Error, Debug)]
+pub enum FeedError {
+    #[error("Unknown topic: {0}")]
+    UnknownTopic(String),
+    #[error("Feed event serialization error")]
+    Parse(#[from] serde_json::Error),
+    #[error("Failed to encode frame: {0}")]
+    Encode(String),
+    #[error("Failed to decode frame: {0}")]
+    Decode(String),
+}
+
+/// Something that happened which live clients may want to hear about
+#[derive(Clone, Debug)]
+pub enum FeedEvent {
+    Squeak(Box<Squeak>),
+    SqueakEdit(SqueakEdit),
+    SqueakDelete(SqueakDelete),
+    SqueakLike(SqueakLike),
+    SqueakUnlike(SqueakUnlike),
+    GameEvent(GameEvent),
+}
+
+impl FeedEvent {
+    /// Parses a raw event stream message into a feed event, if it is one we know about
+    pub fn from_message(subject: &str, payload: &[u8]) -> Result<Option<Self>, FeedError> {
+        let event = if subject == Squeak::get_subject() {
+            Some(FeedEvent::Squeak(serde_json::from_slice(payload)?))
+        } else if subject == SqueakEdit::get_subject() {
+            Some(FeedEvent::SqueakEdit(serde_json::from_slice(payload)?))
+        } else if subject == SqueakDelete::get_subject() {
+            Some(FeedEvent::SqueakDelete(serde_json::from_slice(payload)?))
+        } else if subject == SqueakLike::get_subject() {
+            Some(FeedEvent::SqueakLike(serde_json::from_slice(payload)?))
+        } else if subject == SqueakUnlike::get_subject() {
+            Some(FeedEvent::SqueakUnlike(serde_json::from_slice(payload)?))
+        } else if GameEvent::matches_subject(subject) {
+            Some(FeedEvent::GameEvent(serde_json::from_slice(payload)?))
+        } else {
+            None
+        };
+
+        Ok(event)
+    }
+
+    /// Parses an event read back through an event stream query
+    pub fn from_stored(stored: StoredEvent) -> Result<Option<Self>, FeedError> {
+        let event = if stored.subject == Squeak::get_subject() {
+            Some(FeedEvent::Squeak(serde_json::from_value(stored.payload)?))
+        } else if stored.subject == SqueakEdit::get_subject() {
+            Some(FeedEvent::SqueakEdit(serde_json::from_value(
+                stored.payload,
+            )?))
+        } else if stored.subject == SqueakDelete::get_subject() {
+            Some(FeedEvent::SqueakDelete(serde_json::from_value(
+                stored.payload,
+            )?))
+        } else if stored.subject == SqueakLike::get_subject() {
+            Some(FeedEvent::SqueakLike(serde_json::from_value(
+                stored.payload,
+            )?))
+        } else if stored.subject == SqueakUnlike::get_subject() {
+            Some(FeedEvent::SqueakUnlike(serde_json::from_value(
+                stored.payload,
+            )?))
+        } else if GameEvent::matches_subject(&stored.subject) {
+            Some(FeedEvent::GameEvent(serde_json::from_value(
+                stored.payload,
+            )?))
+        } else {
+            None
+        };
+
+        Ok(event)
+    }
+
+    /// Gets the ULID of the underlying event
+    pub fn id(&self) -> ulid::Ulid {
+        match self {
+            FeedEvent::Squeak(squeak) => squeak.id,
+            FeedEvent::SqueakEdit(edit) => edit.id,
+            FeedEvent::SqueakDelete(delete) => delete.id,
+            FeedEvent::SqueakLike(like) => like.id,
+            FeedEvent::SqueakUnlike(unlike) => unlike.id,
+            FeedEvent::GameEvent(event) => event.id,
+        }
+    }
+
+    /// Gets the subject the event is published on
+    pub fn subject(&self) -> String {
+        match self {
+            FeedEvent::Squeak(_) => Squeak::get_subject(),
+            FeedEvent::SqueakEdit(_) => SqueakEdit::get_subject(),
+            FeedEvent::SqueakDelete(_) => SqueakDelete::get_subject(),
+            FeedEvent::SqueakLike(_) => SqueakLike::get_subject(),
+            FeedEvent::SqueakUnlike(_) => SqueakUnlike::get_subject(),
+            FeedEvent::GameEvent(event) => event.get_subject(),
+        }
+    }
+
+    /// Checks whether the event belongs to a topic
+    pub fn matches(&self, topic: &Topic) -> bool {
+        match (self, topic) {
+            (
+                FeedEvent::Squeak(_)
+                | FeedEvent::SqueakEdit(_)
+                | FeedEvent::SqueakDelete(_)
+                | FeedEvent::SqueakLike(_)
+                | FeedEvent::SqueakUnlike(_),
+                Topic::Skeever,
+            ) => true,
+            (FeedEvent::GameEvent(_), Topic::Game) => true,
+            (FeedEvent::Squeak(squeak), Topic::Character(name)) => squeak.is_by(name),
+            (FeedEvent::SqueakEdit(edit), Topic::Character(name)) => {
+                edit.author.eq_ignore_ascii_case(name)
+            }
+            (FeedEvent::SqueakDelete(delete), Topic::Character(name)) => {
+                delete.author.eq_ignore_ascii_case(name)
+            }
+            (FeedEvent::SqueakLike(like), Topic::Character(name)) => {
+                like.author.eq_ignore_ascii_case(name)
+            }
+            (FeedEvent::SqueakUnlike(unlike), Topic::Character(name)) => {
+                unlike.author.eq_ignore_ascii_case(name)
+            }
+            (FeedEvent::GameEvent(event), Topic::Character(name)) => {
+                event.character.eq_ignore_ascii_case(name)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A stream of feed events clients can subscribe to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub enum Topic {
+    /// Every squeak
+    Skeever,
+    /// Every game event
+    Game,
+    /// Squeaks and game events from a single character
+    Character(String),
+}
+
+impl Topic {
+    /// Gets the event stream subject filter covering this topic
+    pub fn get_subject_filter(&self) -> Vec<String> {
+        match self {
+            Topic::Skeever => vec![Squeak::get_subject_filter()],
+            Topic::Game => vec![GameEvent::get_subject_filter()],
+            Topic::Character(_) => vec![
+                Squeak::get_subject_filter(),
+                GameEvent::get_subject_filter(),
+            ],
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = FeedError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "skeever" => Ok(Topic::Skeever),
+            "game" => Ok(Topic::Game),
+            _ => match value.strip_prefix("character:") {
+                Some(name) if !name.is_empty() => Ok(Topic::Character(name.to_string())),
+                _ => Err(FeedError::UnknownTopic(value.to_string())),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Topic {
+    type Error = FeedError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Topic> for String {
+    fn from(topic: Topic) -> Self {
+        topic.to_string()
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Skeever => write!(f, "skeever"),
+            Topic::Game => write!(f, "game"),
+            Topic::Character(name) => write!(f, "character:{}", name),
+        }
+    }
+}
+
+/// Topics travel as plain strings, so that's how they're described to API clients
+impl utoipa::PartialSchema for Topic {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        utoipa::openapi::ObjectBuilder::new()
+            .schema_type(utoipa::openapi::schema::Type::String)
+            .description(Some("`skeever`, `game` or `character:<name>`"))
+            .examples(["skeever", "game", "character:Lucien"])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for Topic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squeak(author: &str, character: Option<&str>) -> FeedEvent {
+        FeedEvent::Squeak(Box::new(
+            serde_json::from_value(serde_json::json!({
+                "id": ulid::Ulid::new(),
+                "content": "",
+                "author": { "name": author, "avatar_url": "" },
+                "character": character,
+            }))
+            .unwrap(),
+        ))
+    }
+
+    #[test]
+    fn character_topics_follow_the_squeaks_character() {
+        let topic = Topic::Character("Martin".to_string());
+
+        assert!(squeak("someone", Some("martin")).matches(&topic));
+        assert!(!squeak("Martin", Some("Jauffre")).matches(&topic));
+        // Squeaks from before the character was recorded go by their author
+        assert!(squeak("Martin", None).matches(&topic));
+    }
+}
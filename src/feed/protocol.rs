@@ -0,0 +1,212 @@
+//! The versioned websocket protocol spoken between oblivion-server and its clients
+use super::{FeedError, FeedEvent, Topic};
+use crate::{
+    game::event::GameEvent,
+    skeever::{
+        like::{SqueakLike, SqueakUnlike},
+        revision::{SqueakDelete, SqueakEdit},
+        squeak::Squeak,
+    },
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use utoipa::ToSchema;
+
+/// The current protocol version, bumped on breaking changes
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Frames sent from a client to the server
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    /// Start receiving events for these topics
+    Subscribe { topics: Vec<Topic> },
+    /// Stop receiving events for these topics
+    Unsubscribe { topics: Vec<Topic> },
+    /// Replay everything after the last event the client saw
+    Resume { after: ulid::Ulid },
+    /// Application-level heartbeat, answered with a pong
+    Ping { nonce: Option<String> },
+    /// Answer to a server ping
+    Pong { nonce: Option<String> },
+}
+
+/// Frames sent from the server to a client
+///
+/// Clients should ignore frame types they don't recognize, so new event kinds can be added
+/// without a version bump.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    /// First frame on every connection
+    Hello {
+        version: u32,
+        topics: Vec<Topic>,
+    },
+    Squeak(Box<Squeak>),
+    /// A squeak the client may have already received was edited
+    SqueakEdit(SqueakEdit),
+    /// A squeak the client may have already received was deleted
+    SqueakDelete(SqueakDelete),
+    /// A character liked a squeak, carrying its new like count
+    SqueakLike(SqueakLike),
+    /// A character took back their like, carrying the squeak's new like count
+    SqueakUnlike(SqueakUnlike),
+    GameEvent(GameEvent),
+    Subscribed {
+        topics: Vec<Topic>,
+    },
+    Unsubscribed {
+        topics: Vec<Topic>,
+    },
+    /// Sent once a resume replay has finished
+    Resumed {
+        after: ulid::Ulid,
+        replayed: usize,
+    },
+    Ping {
+        nonce: Option<String>,
+    },
+    Pong {
+        nonce: Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl From<FeedEvent> for ServerFrame {
+    fn from(event: FeedEvent) -> Self {
+        match event {
+            FeedEvent::Squeak(squeak) => ServerFrame::Squeak(squeak),
+            FeedEvent::SqueakEdit(edit) => ServerFrame::SqueakEdit(edit),
+            FeedEvent::SqueakDelete(delete) => ServerFrame::SqueakDelete(delete),
+            FeedEvent::SqueakLike(like) => ServerFrame::SqueakLike(like),
+            FeedEvent::SqueakUnlike(unlike) => ServerFrame::SqueakUnlike(unlike),
+            FeedEvent::GameEvent(event) => ServerFrame::GameEvent(event),
+        }
+    }
+}
+
+/// How frames are serialized, negotiated with the `Sec-WebSocket-Protocol` header
+///
+/// Clients that don't ask for a subprotocol get JSON text frames. Binary encodings are sent as
+/// binary frames, and clients may send their own frames either in the negotiated encoding as
+/// binary or as JSON text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Encoding {
+    /// Every encoding, in the order the server prefers them
+    pub const ALL: [Encoding; 3] = [Encoding::MessagePack, Encoding::Cbor, Encoding::Json];
+
+    /// The websocket subprotocol naming this encoding
+    pub fn subprotocol(&self) -> &'static str {
+        match self {
+            Encoding::Json => "oblivion.v1.json",
+            Encoding::MessagePack => "oblivion.v1.msgpack",
+            Encoding::Cbor => "oblivion.v1.cbor",
+        }
+    }
+
+    /// Picks our preferred encoding out of a `Sec-WebSocket-Protocol` header
+    pub fn negotiate(offered: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|encoding| {
+            offered
+                .split(',')
+                .any(|protocol| protocol.trim() == encoding.subprotocol())
+        })
+    }
+
+    /// Whether frames in this encoding go out as binary websocket messages
+    pub fn is_binary(&self) -> bool {
+        !matches!(self, Encoding::Json)
+    }
+
+    /// Serializes a frame
+    pub fn encode<T: Serialize>(&self, frame: &T) -> Result<Vec<u8>, FeedError> {
+        match self {
+            Encoding::Json => Ok(serde_json::to_vec(frame)?),
+            // Named fields keep the `type` tag readable to clients
+            Encoding::MessagePack => {
+                rmp_serde::to_vec_named(frame).map_err(|e| FeedError::Encode(e.to_string()))
+            }
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(frame, &mut bytes)
+                    .map_err(|e| FeedError::Encode(e.to_string()))?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Deserializes a frame
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, FeedError> {
+        match self {
+            Encoding::Json => Ok(serde_json::from_slice(bytes)?),
+            Encoding::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|e| FeedError::Decode(e.to_string()))
+            }
+            Encoding::Cbor => {
+                ciborium::from_reader(bytes).map_err(|e| FeedError::Decode(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_prefers_binary_encodings() {
+        assert_eq!(
+            Encoding::negotiate("oblivion.v1.json, oblivion.v1.cbor, oblivion.v1.msgpack"),
+            Some(Encoding::MessagePack)
+        );
+        assert_eq!(
+            Encoding::negotiate("oblivion.v1.json,oblivion.v1.cbor"),
+            Some(Encoding::Cbor)
+        );
+        assert_eq!(
+            Encoding::negotiate("oblivion.v1.json"),
+            Some(Encoding::Json)
+        );
+    }
+
+    #[test]
+    fn negotiate_ignores_unknown_protocols() {
+        assert_eq!(
+            Encoding::negotiate("graphql-ws,  oblivion.v1.cbor "),
+            Some(Encoding::Cbor)
+        );
+        assert_eq!(Encoding::negotiate("graphql-ws"), None);
+        assert_eq!(Encoding::negotiate(""), None);
+    }
+
+    #[test]
+    fn negotiate_matches_whole_protocol_names() {
+        assert_eq!(Encoding::negotiate("oblivion.v1.msgpack2"), None);
+        assert_eq!(Encoding::negotiate("oblivion.v2.json"), None);
+        assert_eq!(Encoding::negotiate("OBLIVION.V1.JSON"), None);
+    }
+
+    #[test]
+    fn frames_round_trip_in_every_encoding() {
+        let frame = ClientFrame::Ping {
+            nonce: Some("abc".to_string()),
+        };
+        for encoding in Encoding::ALL {
+            let bytes = encoding.encode(&frame).unwrap();
+            let decoded: ClientFrame = encoding.decode(&bytes).unwrap();
+            assert!(
+                matches!(decoded, ClientFrame::Ping { nonce: Some(ref nonce) } if nonce == "abc"),
+                "{encoding:?} didn't round trip"
+            );
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! A GameEvent is something that happened to a character inside Oblivion, reported by the game mod
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::config::OddbotConfig;
+
+/// Longest character name we accept from the game
+const MAX_CHARACTER_LENGTH: usize = 64;
+/// Longest free-form text (quest, location, cause) we accept from the game
+const MAX_TEXT_LENGTH: usize = 256;
+/// Longest chat line we accept from the game, matching Discord's message limit
+const MAX_CHAT_LENGTH: usize = 2000;
+
+#[derive(Error, Debug)]
+pub enum GameEventError {
+    #[error("Character is required")]
+    CharacterRequired,
+    #[error("Event kind is required")]
+    KindRequired,
+    #[error("Invalid game event: {0}")]
+    Invalid(String),
+}
+
+/// The different kinds of things the game mod can report
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GameEventKind {
+    LevelUp { level: u32 },
+    Death { cause: Option<String> },
+    QuestCompleted { quest: String },
+    LocationChanged { location: String },
+    Chat { message: String },
+}
+
+impl GameEventKind {
+    /// Gets the snake_case name of the kind, used in subjects and payloads
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameEventKind::LevelUp { .. } => "level_up",
+            GameEventKind::Death { .. } => "death",
+            GameEventKind::QuestCompleted { .. } => "quest_completed",
+            GameEventKind::LocationChanged { .. } => "location_changed",
+            GameEventKind::Chat { .. } => "chat",
+        }
+    }
+
+    /// Validates the data carried by the event kind
+    pub fn validate(&self) -> Result<(), GameEventError> {
+        match self {
+            GameEventKind::LevelUp { level } => {
+                if *level == 0 {
+                    return Err(GameEventError::Invalid("level must be at least 1".into()));
+                }
+            }
+            GameEventKind::Death { cause } => {
+                if let Some(cause) = cause {
+                    validate_text("cause", cause, MAX_TEXT_LENGTH)?;
+                }
+            }
+            GameEventKind::QuestCompleted { quest } => {
+                validate_text("quest", quest, MAX_TEXT_LENGTH)?
+            }
+            GameEventKind::LocationChanged { location } => {
+                validate_text("location", location, MAX_TEXT_LENGTH)?
+            }
+            GameEventKind::Chat { message } => validate_text("message", message, MAX_CHAT_LENGTH)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Makes sure a text field is neither blank nor too long
+fn validate_text(field: &str, value: &str, max_length: usize) -> Result<(), GameEventError> {
+    if value.trim().is_empty() {
+        return Err(GameEventError::Invalid(format!(
+            "{field} must not be empty"
+        )));
+    }
+
+    if value.chars().count() > max_length {
+        return Err(GameEventError::Invalid(format!(
+            "{field} must be at most {max_length} characters"
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct GameEventBuilder {
+    character: Option<String>,
+    kind: Option<GameEventKind>,
+}
+
+impl GameEventBuilder {
+    /// Sets the name of the character the event happened to
+    pub fn character(mut self, name: String) -> Self {
+        self.character = Some(name);
+        self
+    }
+
+    /// Sets what happened
+    pub fn kind(mut self, kind: GameEventKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Validates and builds the game event
+    pub fn build(self) -> Result<GameEvent, GameEventError> {
+        let Some(character) = self.character else {
+            return Err(GameEventError::CharacterRequired);
+        };
+        validate_text("character", &character, MAX_CHARACTER_LENGTH)?;
+
+        let Some(kind) = self.kind else {
+            return Err(GameEventError::KindRequired);
+        };
+        kind.validate()?;
+
+        Ok(GameEvent {
+            id: ulid::Ulid::new(),
+            character,
+            kind,
+        })
+    }
+}
+
+impl IntoFuture for GameEventBuilder {
+    type Output = Result<GameEvent, GameEventError>;
+    type IntoFuture = futures::future::Ready<Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        futures::future::ready(self.build())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct GameEvent {
+    pub id: ulid::Ulid,
+    pub character: String,
+    #[serde(flatten)]
+    pub kind: GameEventKind,
+}
+
+impl GameEvent {
+    pub fn builder() -> GameEventBuilder {
+        GameEventBuilder::default()
+    }
+
+    /// Gets the subject for this event, e.g. `oddlaws.events.game.level_up`
+    pub fn get_subject(&self) -> String {
+        format!("{}.{}", Self::get_subject_root(), self.kind.name())
+    }
+
+    /// Gets the subject filter matching every game event
+    pub fn get_subject_filter() -> String {
+        format!("{}.>", Self::get_subject_root())
+    }
+
+    /// Checks whether a subject belongs to a game event
+    pub fn matches_subject(subject: &str) -> bool {
+        subject
+            .strip_prefix(&Self::get_subject_root())
+            .is_some_and(|kind| kind.starts_with('.'))
+    }
+
+    fn get_subject_root() -> String {
+        let prefix =
+            OddbotConfig::get_event_stream_prefix().unwrap_or("oddlaws.events".to_string());
+        format!("{}.game", prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_invalid(result: Result<(), GameEventError>) -> bool {
+        matches!(result, Err(GameEventError::Invalid(_)))
+    }
+
+    #[test]
+    fn blank_text_is_invalid() {
+        assert!(is_invalid(validate_text("quest", "", MAX_TEXT_LENGTH)));
+        assert!(is_invalid(validate_text(
+            "quest",
+            "  \n\t",
+            MAX_TEXT_LENGTH
+        )));
+    }
+
+    #[test]
+    fn text_may_be_exactly_the_limit_but_no_longer() {
+        assert!(validate_text("quest", &"a".repeat(MAX_TEXT_LENGTH), MAX_TEXT_LENGTH).is_ok());
+        assert!(is_invalid(validate_text(
+            "quest",
+            &"a".repeat(MAX_TEXT_LENGTH + 1),
+            MAX_TEXT_LENGTH
+        )));
+    }
+
+    #[test]
+    fn text_length_counts_characters_not_bytes() {
+        assert!(validate_text("quest", &"é".repeat(MAX_TEXT_LENGTH), MAX_TEXT_LENGTH).is_ok());
+    }
+
+    #[test]
+    fn level_ups_need_a_level() {
+        assert!(is_invalid(GameEventKind::LevelUp { level: 0 }.validate()));
+        assert!(GameEventKind::LevelUp { level: 1 }.validate().is_ok());
+    }
+
+    #[test]
+    fn deaths_may_have_no_cause() {
+        assert!(GameEventKind::Death { cause: None }.validate().is_ok());
+        assert!(
+            GameEventKind::Death {
+                cause: Some("A mudcrab".to_string())
+            }
+            .validate()
+            .is_ok()
+        );
+        assert!(is_invalid(
+            GameEventKind::Death {
+                cause: Some(" ".to_string())
+            }
+            .validate()
+        ));
+    }
+
+    #[test]
+    fn quests_and_locations_need_text() {
+        assert!(
+            GameEventKind::QuestCompleted {
+                quest: "Paradise".to_string()
+            }
+            .validate()
+            .is_ok()
+        );
+        assert!(is_invalid(
+            GameEventKind::QuestCompleted {
+                quest: String::new()
+            }
+            .validate()
+        ));
+        assert!(
+            GameEventKind::LocationChanged {
+                location: "Kvatch".to_string()
+            }
+            .validate()
+            .is_ok()
+        );
+        assert!(is_invalid(
+            GameEventKind::LocationChanged {
+                location: "a".repeat(MAX_TEXT_LENGTH + 1)
+            }
+            .validate()
+        ));
+    }
+
+    #[test]
+    fn chat_allows_longer_text() {
+        let message = "a".repeat(MAX_CHAT_LENGTH);
+        assert!(GameEventKind::Chat { message }.validate().is_ok());
+
+        let message = "a".repeat(MAX_CHAT_LENGTH + 1);
+        assert!(is_invalid(GameEventKind::Chat { message }.validate()));
+    }
+
+    #[test]
+    fn builder_needs_a_character_and_a_kind() {
+        let kind = GameEventKind::LevelUp { level: 2 };
+        assert!(matches!(
+            GameEvent::builder().kind(kind.clone()).build(),
+            Err(GameEventError::CharacterRequired)
+        ));
+        assert!(matches!(
+            GameEvent::builder()
+                .character("Jauffre".to_string())
+                .build(),
+            Err(GameEventError::KindRequired)
+        ));
+        assert!(matches!(
+            GameEvent::builder()
+                .character("a".repeat(MAX_CHARACTER_LENGTH + 1))
+                .kind(kind.clone())
+                .build(),
+            Err(GameEventError::Invalid(_))
+        ));
+
+        let event = GameEvent::builder()
+            .character("Jauffre".to_string())
+            .kind(kind)
+            .build()
+            .unwrap();
+        assert_eq!(event.character, "Jauffre");
+    }
+}
@@ -1,8 +1,12 @@
+pub mod auth;
 pub mod config;
 pub mod db;
 pub mod discord;
 pub mod error;
 pub mod event_stream;
+pub mod feed;
+pub mod game;
 pub mod nats;
 pub mod prelude;
+pub mod schema;
 pub mod skeever;
@@ -2,4 +2,5 @@ pub use crate::config::OddbotConfig;
 pub use crate::discord::bot::DiscordBot;
 pub use crate::error::OddbotError;
 pub use crate::event_stream::message::EventMessage;
+pub use crate::event_stream::query::{EventCursor, EventPage, EventQuery, StoredEvent};
 pub use crate::event_stream::stream::EventStream;
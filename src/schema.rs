@@ -0,0 +1,178 @@
+//! JSON Schemas for the payloads oddbot publishes and oblivion-server serves
+use crate::{
+    discord::character::CharacterProfile,
+    event_stream::{
+        message::EventMessage,
+        query::{EventPage, StoredEvent},
+    },
+    feed::{
+        Topic,
+        protocol::{ClientFrame, ServerFrame},
+    },
+    game::event::{GameEvent, GameEventKind},
+    skeever::{
+        like::{SqueakLike, SqueakUnlike},
+        revision::{SqueakDelete, SqueakEdit},
+        squeak::{Squeak, User},
+    },
+};
+use serde_json::{Map, Value, json};
+use utoipa::OpenApi;
+
+/// Where OpenAPI keeps schemas, which JSON Schema tools know as `$defs`
+const OPENAPI_REF_PREFIX: &str = "#/components/schemas/";
+const JSON_SCHEMA_REF_PREFIX: &str = "#/$defs/";
+
+/// Every payload type, as OpenAPI components
+#[derive(OpenApi)]
+#[openapi(components(schemas(
+    Squeak,
+    User,
+    GameEvent,
+    GameEventKind,
+    EventMessage<Squeak>,
+    EventMessage<GameEvent>,
+    SqueakEdit,
+    SqueakDelete,
+    EventMessage<SqueakEdit>,
+    EventMessage<SqueakDelete>,
+    SqueakLike,
+    SqueakUnlike,
+    EventMessage<SqueakLike>,
+    EventMessage<SqueakUnlike>,
+    StoredEvent,
+    EventPage,
+    Topic,
+    ClientFrame,
+    ServerFrame,
+    CharacterProfile,
+)))]
+pub struct PayloadSchemas;
+
+/// Builds a standalone JSON Schema for each payload type, keyed by type name
+///
+/// Each schema carries the definitions it refers to under `$defs`, so it can be used on its own.
+pub fn json_schemas() -> Vec<(String, Value)> {
+    let openapi = PayloadSchemas::openapi();
+    let components = openapi
+        .components
+        .map(|components| components.schemas)
+        .unwrap_or_default();
+
+    let mut defs = serde_json::to_value(&components).unwrap_or_default();
+    rewrite_refs(&mut defs);
+    let Value::Object(defs) = defs else {
+        return Vec::new();
+    };
+
+    components
+        .keys()
+        .map(|name| {
+            let schema = json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "$id": format!("{name}.schema.json"),
+                "$ref": format!("{JSON_SCHEMA_REF_PREFIX}{name}"),
+                "$defs": referenced_defs(&defs, name),
+            });
+            (name.clone(), schema)
+        })
+        .collect()
+}
+
+/// Picks out a definition and every definition it refers to, directly or not
+fn referenced_defs(defs: &Map<String, Value>, name: &str) -> Map<String, Value> {
+    let mut referenced = Map::new();
+    let mut pending = vec![name.to_string()];
+    while let Some(name) = pending.pop() {
+        if referenced.contains_key(&name) {
+            continue;
+        }
+        let Some(def) = defs.get(&name) else {
+            continue;
+        };
+        collect_refs(def, &mut pending);
+        referenced.insert(name, def.clone());
+    }
+    referenced
+}
+
+/// Collects the names of the `$defs` a schema refers to
+fn collect_refs(value: &Value, names: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                match value {
+                    Value::String(reference) if key == "$ref" => {
+                        if let Some(name) = reference.strip_prefix(JSON_SCHEMA_REF_PREFIX) {
+                            names.push(name.to_string());
+                        }
+                    }
+                    value => collect_refs(value, names),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_refs(value, names)),
+        _ => {}
+    }
+}
+
+/// Points OpenAPI component references at `$defs` instead
+fn rewrite_refs(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    Value::String(reference) if key == "$ref" => {
+                        if let Some(name) = reference.strip_prefix(OPENAPI_REF_PREFIX) {
+                            *reference = format!("{JSON_SCHEMA_REF_PREFIX}{name}");
+                        }
+                    }
+                    value => rewrite_refs(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(rewrite_refs),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defs_of(schemas: &[(String, Value)], name: &str) -> Vec<String> {
+        let (_, schema) = schemas.iter().find(|(key, _)| key == name).unwrap();
+        let mut defs: Vec<String> = schema["$defs"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        defs.sort();
+        defs
+    }
+
+    #[test]
+    fn schemas_only_carry_the_definitions_they_refer_to() {
+        let schemas = json_schemas();
+
+        assert_eq!(defs_of(&schemas, "User"), vec!["User"]);
+        assert!(defs_of(&schemas, "Squeak").contains(&"User".to_string()));
+        assert!(!defs_of(&schemas, "Squeak").contains(&"GameEvent".to_string()));
+    }
+
+    #[test]
+    fn every_reference_resolves() {
+        for (name, schema) in json_schemas() {
+            let defs = schema["$defs"].as_object().unwrap();
+            let mut refs = Vec::new();
+            collect_refs(&schema, &mut refs);
+            for reference in refs {
+                assert!(
+                    defs.contains_key(&reference),
+                    "{name} refers to missing {reference}"
+                );
+            }
+        }
+    }
+}
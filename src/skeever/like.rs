@@ -0,0 +1,229 @@
+//! Likes are characters reacting to squeaks with the configured emoji on Discord
+use crate::error::OddbotError;
+use async_nats::jetstream::{self, context::CreateKeyValueError, kv};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::squeak::Squeak;
+
+/// How many times we retry a like when someone else changed the squeak's likes first
+const MAX_UPDATE_ATTEMPTS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum LikeError {
+    #[error("Could not create key-value store")]
+    CreateStore(#[from] CreateKeyValueError),
+    #[error("Failed to get likes")]
+    GetLikes(#[from] kv::EntryError),
+    #[error("Failed to save likes")]
+    SaveLikes(#[from] kv::UpdateError),
+    #[error("Failed to save likes")]
+    CreateLikes(#[from] kv::CreateError),
+    #[error("Failed to watch likes")]
+    WatchLikes(#[from] kv::WatchError),
+    #[error("Likes serialization error")]
+    ParseLikes(#[from] serde_json::Error),
+    #[error("Likes kept changing while we updated them")]
+    Contended,
+}
+
+/// A character liked a squeak
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct SqueakLike {
+    /// ID of the like itself
+    pub id: ulid::Ulid,
+    pub squeak_id: ulid::Ulid,
+    /// Name of the squeak's author, so character topics can follow the like
+    pub author: String,
+    /// Name of the character who liked the squeak
+    pub character: String,
+    /// How many characters like the squeak now
+    pub likes: u64,
+}
+
+impl SqueakLike {
+    pub fn new(squeak_id: ulid::Ulid, author: String, character: String, likes: u64) -> Self {
+        Self {
+            id: ulid::Ulid::new(),
+            squeak_id,
+            author,
+            character,
+            likes,
+        }
+    }
+
+    pub fn get_subject() -> String {
+        format!("{}.like", Squeak::get_subject_root())
+    }
+}
+
+/// A character took back their like of a squeak
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct SqueakUnlike {
+    /// ID of the unlike itself
+    pub id: ulid::Ulid,
+    pub squeak_id: ulid::Ulid,
+    /// Name of the squeak's author, so character topics can follow the unlike
+    pub author: String,
+    /// Name of the character who took back their like
+    pub character: String,
+    /// How many characters like the squeak now
+    pub likes: u64,
+}
+
+impl SqueakUnlike {
+    pub fn new(squeak_id: ulid::Ulid, author: String, character: String, likes: u64) -> Self {
+        Self {
+            id: ulid::Ulid::new(),
+            squeak_id,
+            author,
+            character,
+            likes,
+        }
+    }
+
+    pub fn get_subject() -> String {
+        format!("{}.unlike", Squeak::get_subject_root())
+    }
+}
+
+/// The characters who like a squeak
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Likes {
+    pub characters: Vec<String>,
+}
+
+impl Likes {
+    pub fn count(&self) -> u64 {
+        self.characters.len() as u64
+    }
+}
+
+/// A LikeStore keeps who likes each squeak, backed by a key-value store keyed by squeak ID
+#[derive(Debug)]
+pub struct LikeStore {
+    store: kv::Store,
+}
+
+impl LikeStore {
+    /// Creates a new like store instance
+    pub async fn new(client: async_nats::Client) -> Result<Self, OddbotError> {
+        let store = jetstream::new(client)
+            .create_key_value(kv::Config {
+                bucket: "skeever_likes".to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(LikeError::CreateStore)?;
+
+        Ok(LikeStore { store })
+    }
+
+    /// Gets the characters who like a squeak
+    pub async fn get(&self, squeak_id: ulid::Ulid) -> Result<Likes, OddbotError> {
+        let likes = match self
+            .store
+            .get(squeak_id.to_string())
+            .await
+            .map_err(LikeError::GetLikes)?
+        {
+            Some(value) => serde_json::from_slice(&value).map_err(LikeError::ParseLikes)?,
+            None => Likes::default(),
+        };
+
+        Ok(likes)
+    }
+
+    /// Likes a squeak, returning the new count unless the character already liked it
+    pub async fn like(
+        &self,
+        squeak_id: ulid::Ulid,
+        character: &str,
+    ) -> Result<Option<u64>, OddbotError> {
+        self.change(squeak_id, |likes| {
+            if likes.characters.iter().any(|liked| liked == character) {
+                return false;
+            }
+            likes.characters.push(character.to_string());
+            true
+        })
+        .await
+    }
+
+    /// Takes back a like, returning the new count unless the character didn't like the squeak
+    pub async fn unlike(
+        &self,
+        squeak_id: ulid::Ulid,
+        character: &str,
+    ) -> Result<Option<u64>, OddbotError> {
+        self.change(squeak_id, |likes| {
+            let before = likes.characters.len();
+            likes.characters.retain(|liked| liked != character);
+            likes.characters.len() != before
+        })
+        .await
+    }
+
+    /// Takes back every like of a squeak, returning the characters who liked it
+    pub async fn clear(&self, squeak_id: ulid::Ulid) -> Result<Vec<String>, OddbotError> {
+        let mut cleared = Vec::new();
+        self.change(squeak_id, |likes| {
+            cleared = std::mem::take(&mut likes.characters);
+            !cleared.is_empty()
+        })
+        .await?;
+
+        Ok(cleared)
+    }
+
+    /// Applies a change to a squeak's likes, retrying when it races with another change
+    async fn change(
+        &self,
+        squeak_id: ulid::Ulid,
+        mut change: impl FnMut(&mut Likes) -> bool,
+    ) -> Result<Option<u64>, OddbotError> {
+        let key = squeak_id.to_string();
+
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let entry = self.store.entry(&key).await.map_err(LikeError::GetLikes)?;
+            let mut likes = match &entry {
+                Some(entry) if entry.operation == kv::Operation::Put => {
+                    serde_json::from_slice(&entry.value).map_err(LikeError::ParseLikes)?
+                }
+                _ => Likes::default(),
+            };
+            if !change(&mut likes) {
+                return Ok(None);
+            }
+
+            let value = serde_json::to_vec(&likes).map_err(LikeError::ParseLikes)?;
+            let saved = match &entry {
+                Some(entry) => match self.store.update(&key, value.into(), entry.revision).await {
+                    Ok(_) => true,
+                    Err(e) if e.kind() == kv::UpdateErrorKind::WrongLastRevision => false,
+                    Err(e) => return Err(LikeError::SaveLikes(e).into()),
+                },
+                None => match self.store.create(&key, value.into()).await {
+                    Ok(_) => true,
+                    Err(e) if e.kind() == kv::CreateErrorKind::AlreadyExists => false,
+                    Err(e) => return Err(LikeError::CreateLikes(e).into()),
+                },
+            };
+            if saved {
+                return Ok(Some(likes.count()));
+            }
+        }
+
+        Err(LikeError::Contended.into())
+    }
+
+    /// Watches every squeak's likes, starting with their current values
+    pub async fn watch(&self) -> Result<kv::Watch, OddbotError> {
+        Ok(self
+            .store
+            .watch_with_history(">")
+            .await
+            .map_err(LikeError::WatchLikes)?)
+    }
+}
@@ -1 +1,5 @@
+pub mod like;
+pub mod origin;
+pub mod resqueak;
+pub mod revision;
 pub mod squeak;
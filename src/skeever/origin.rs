@@ -0,0 +1,100 @@
+//! Remembers which Discord message each squeak was posted as, so edits and deletions can follow
+use crate::error::OddbotError;
+use async_nats::jetstream::{self, context::CreateKeyValueError, kv};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::squeak::Squeak;
+
+#[derive(Error, Debug)]
+pub enum OriginError {
+    #[error("Could not create key-value store")]
+    CreateStore(#[from] CreateKeyValueError),
+    #[error("Failed to save squeak origin")]
+    SaveOrigin(#[from] kv::PutError),
+    #[error("Failed to get squeak origin")]
+    GetOrigin(#[from] kv::EntryError),
+    #[error("Failed to delete squeak origin")]
+    DeleteOrigin(#[from] kv::DeleteError),
+    #[error("Squeak origin serialization error")]
+    ParseOrigin(#[from] serde_json::Error),
+}
+
+/// The squeak a Discord message was published as
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SqueakOrigin {
+    pub squeak_id: ulid::Ulid,
+    pub author: String,
+    /// The squeak that started the conversation, missing on origins recorded before threads
+    #[serde(default)]
+    pub thread_id: Option<ulid::Ulid>,
+}
+
+impl SqueakOrigin {
+    /// The squeak that started the conversation this one is part of
+    pub fn thread_root(&self) -> ulid::Ulid {
+        self.thread_id.unwrap_or(self.squeak_id)
+    }
+}
+
+/// A SqueakOriginStore maps Discord message IDs to squeaks, backed by a key-value store
+#[derive(Debug)]
+pub struct SqueakOriginStore {
+    store: kv::Store,
+}
+
+impl SqueakOriginStore {
+    /// Creates a new squeak origin store instance
+    pub async fn new(client: async_nats::Client) -> Result<Self, OddbotError> {
+        let store = jetstream::new(client)
+            .create_key_value(kv::Config {
+                bucket: "skeever_discord_messages".to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(OriginError::CreateStore)?;
+
+        Ok(SqueakOriginStore { store })
+    }
+
+    /// Remembers the squeak a Discord message was published as
+    pub async fn record(&self, message_id: &str, squeak: &Squeak) -> Result<(), OddbotError> {
+        let origin = SqueakOrigin {
+            squeak_id: squeak.id,
+            author: squeak.author.name.clone(),
+            thread_id: squeak.thread_id,
+        };
+        let value = serde_json::to_vec(&origin).map_err(OriginError::ParseOrigin)?;
+        self.store
+            .put(message_id, value.into())
+            .await
+            .map_err(OriginError::SaveOrigin)?;
+
+        Ok(())
+    }
+
+    /// Gets the squeak a Discord message was published as, if it was one
+    pub async fn get(&self, message_id: &str) -> Result<Option<SqueakOrigin>, OddbotError> {
+        let Some(value) = self
+            .store
+            .get(message_id)
+            .await
+            .map_err(OriginError::GetOrigin)?
+        else {
+            return Ok(None);
+        };
+
+        let origin = serde_json::from_slice(&value).map_err(OriginError::ParseOrigin)?;
+        Ok(Some(origin))
+    }
+
+    /// Forgets a Discord message once its squeak is deleted
+    pub async fn remove(&self, message_id: &str) -> Result<(), OddbotError> {
+        self.store
+            .delete(message_id)
+            .await
+            .map_err(OriginError::DeleteOrigin)?;
+
+        Ok(())
+    }
+}
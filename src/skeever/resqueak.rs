@@ -0,0 +1,184 @@
+//! Resqueaks are characters re-sharing a squeak, from a Discord reaction or the message's context menu
+use crate::error::OddbotError;
+use async_nats::jetstream::{self, context::CreateKeyValueError, kv};
+use futures::StreamExt;
+use thiserror::Error;
+
+use super::{origin::SqueakOrigin, squeak::Squeak};
+
+#[derive(Error, Debug)]
+pub enum ResqueakError {
+    #[error("Could not create key-value store")]
+    CreateStore(#[from] CreateKeyValueError),
+    #[error("Failed to save resqueak")]
+    SaveResqueak(#[from] kv::CreateError),
+    #[error("Failed to get resqueak")]
+    GetResqueak(#[from] kv::EntryError),
+    #[error("Failed to delete resqueak")]
+    DeleteResqueak(#[from] kv::DeleteError),
+    #[error("Failed to list resqueaks")]
+    ListResqueaks(#[from] kv::WatchError),
+    #[error("Failed to read resqueaks")]
+    ReadResqueaks(#[from] kv::WatcherError),
+    #[error("Resqueak serialization error")]
+    ParseResqueak(#[from] serde_json::Error),
+}
+
+/// A ResqueakStore remembers which squeaks each Discord user resqueaked by reacting, backed by a key-value store
+///
+/// This keeps a reaction from resqueaking twice, and lets removing it take the resqueak back.
+#[derive(Debug)]
+pub struct ResqueakStore {
+    store: kv::Store,
+}
+
+impl ResqueakStore {
+    /// Creates a new resqueak store instance
+    pub async fn new(client: async_nats::Client) -> Result<Self, OddbotError> {
+        let store = jetstream::new(client)
+            .create_key_value(kv::Config {
+                bucket: "skeever_resqueaks".to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(ResqueakError::CreateStore)?;
+
+        Ok(ResqueakStore { store })
+    }
+
+    /// Remembers a user's resqueak of a squeak, returning false if they already resqueaked it
+    pub async fn record(
+        &self,
+        original_id: ulid::Ulid,
+        discord_id: &str,
+        resqueak: &Squeak,
+    ) -> Result<bool, OddbotError> {
+        let origin = SqueakOrigin {
+            squeak_id: resqueak.id,
+            author: resqueak.author.name.clone(),
+            thread_id: None,
+        };
+        let value = serde_json::to_vec(&origin).map_err(ResqueakError::ParseResqueak)?;
+
+        match self
+            .store
+            .create(Self::key(original_id, discord_id), value.into())
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == kv::CreateErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(ResqueakError::SaveResqueak(e).into()),
+        }
+    }
+
+    /// Forgets a user's resqueak of a squeak, returning the resqueak if there was one
+    pub async fn take(
+        &self,
+        original_id: ulid::Ulid,
+        discord_id: &str,
+    ) -> Result<Option<SqueakOrigin>, OddbotError> {
+        self.take_key(&Self::key(original_id, discord_id)).await
+    }
+
+    /// Forgets every user's resqueak of a squeak, returning the resqueaks there were
+    pub async fn take_all(
+        &self,
+        original_id: ulid::Ulid,
+    ) -> Result<Vec<SqueakOrigin>, OddbotError> {
+        let mut entries = self
+            .store
+            .watch_with_history(format!("{original_id}.*"))
+            .await
+            .map_err(ResqueakError::ListResqueaks)?;
+
+        // The watch ends straight away when there's nothing to list, otherwise we stop once it
+        // has caught up with the current values
+        let mut keys = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let entry = entry.map_err(ResqueakError::ReadResqueaks)?;
+            if entry.operation == kv::Operation::Put {
+                keys.push(entry.key);
+            }
+            if entry.seen_current {
+                break;
+            }
+        }
+
+        let mut resqueaks = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(resqueak) = self.take_key(&key).await? {
+                resqueaks.push(resqueak);
+            }
+        }
+        Ok(resqueaks)
+    }
+
+    async fn take_key(&self, key: &str) -> Result<Option<SqueakOrigin>, OddbotError> {
+        let Some(value) = self
+            .store
+            .get(key)
+            .await
+            .map_err(ResqueakError::GetResqueak)?
+        else {
+            return Ok(None);
+        };
+
+        self.store
+            .delete(key)
+            .await
+            .map_err(ResqueakError::DeleteResqueak)?;
+
+        let resqueak = serde_json::from_slice(&value).map_err(ResqueakError::ParseResqueak)?;
+        Ok(Some(resqueak))
+    }
+
+    fn key(original_id: ulid::Ulid, discord_id: &str) -> String {
+        format!("{original_id}.{discord_id}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resqueak(name: &str) -> Squeak {
+        Squeak::builder()
+            .user(name.to_string())
+            .avatar("https://cdn.discordapp.com/embed/avatars/0.png".to_string())
+            .content("Praise Sithis".to_string())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server at NATS_URL"]
+    async fn taking_all_resqueaks_only_takes_the_squeaks_own() {
+        let client = crate::nats::create_nats_client().await.unwrap();
+        let store = ResqueakStore::new(client).await.unwrap();
+        let original = ulid::Ulid::new();
+        let other = ulid::Ulid::new();
+
+        store
+            .record(original, "1", &resqueak("Lucien"))
+            .await
+            .unwrap();
+        store
+            .record(original, "2", &resqueak("Vicente"))
+            .await
+            .unwrap();
+        store.record(other, "1", &resqueak("Lucien")).await.unwrap();
+
+        let mut authors: Vec<String> = store
+            .take_all(original)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|resqueak| resqueak.author)
+            .collect();
+        authors.sort();
+        assert_eq!(authors, ["Lucien", "Vicente"]);
+
+        assert!(store.take_all(original).await.unwrap().is_empty());
+        assert!(store.take(other, "1").await.unwrap().is_some());
+    }
+}
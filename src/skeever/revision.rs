@@ -0,0 +1,72 @@
+//! Edits and deletions of squeaks that were already published
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use super::squeak::Squeak;
+
+/// New content for a published squeak
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct SqueakEdit {
+    /// ID of the edit itself
+    pub id: ulid::Ulid,
+    /// The squeak being edited
+    pub squeak_id: ulid::Ulid,
+    /// Name of the squeak's author, so character topics can follow the edit
+    pub author: String,
+    pub content: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub edited_at: OffsetDateTime,
+}
+
+impl SqueakEdit {
+    pub fn new(squeak_id: ulid::Ulid, author: String, content: String) -> Self {
+        Self {
+            id: ulid::Ulid::new(),
+            squeak_id,
+            author,
+            content,
+            edited_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Sets when the squeak was edited, defaulting to now
+    pub fn edited_at(mut self, value: OffsetDateTime) -> Self {
+        self.edited_at = value;
+        self
+    }
+
+    pub fn get_subject() -> String {
+        format!("{}.edit", Squeak::get_subject_root())
+    }
+}
+
+/// A tombstone for a published squeak
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct SqueakDelete {
+    /// ID of the deletion itself
+    pub id: ulid::Ulid,
+    /// The squeak being deleted
+    pub squeak_id: ulid::Ulid,
+    /// Name of the squeak's author, so character topics can follow the deletion
+    pub author: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub deleted_at: OffsetDateTime,
+}
+
+impl SqueakDelete {
+    pub fn new(squeak_id: ulid::Ulid, author: String) -> Self {
+        Self {
+            id: ulid::Ulid::new(),
+            squeak_id,
+            author,
+            deleted_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    pub fn get_subject() -> String {
+        format!("{}.delete", Squeak::get_subject_root())
+    }
+}
@@ -1,12 +1,28 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
 
-use crate::config::OddbotConfig;
+use super::revision::SqueakEdit;
+use crate::{config::OddbotConfig, event_stream::query::ulid_created_at};
 
+/// The current squeak schema version, bumped whenever fields are added or change meaning
+pub const SQUEAK_SCHEMA_VERSION: u32 = 5;
+
+#[derive(Default)]
 pub struct SqueakBuilder {
     content: Option<String>,
     user_name: Option<String>,
     avatar_url: Option<String>,
+    created_at: Option<OffsetDateTime>,
+    edited_at: Option<OffsetDateTime>,
+    source: SqueakSource,
+    discord_message_id: Option<String>,
+    discord_channel_id: Option<String>,
+    character: Option<String>,
+    parent_id: Option<ulid::Ulid>,
+    thread_id: Option<ulid::Ulid>,
+    resqueak_of: Option<ulid::Ulid>,
 }
 
 #[derive(Error, Debug)]
@@ -38,6 +54,50 @@ impl SqueakBuilder {
         self
     }
 
+    /// Sets when the squeak was created, defaulting to now
+    pub fn created_at(mut self, value: OffsetDateTime) -> Self {
+        self.created_at = Some(value);
+        self
+    }
+
+    /// Sets when the squeak was last edited
+    pub fn edited_at(mut self, value: OffsetDateTime) -> Self {
+        self.edited_at = Some(value);
+        self
+    }
+
+    /// Sets where the squeak was posted from
+    pub fn source(mut self, value: SqueakSource) -> Self {
+        self.source = value;
+        self
+    }
+
+    /// Sets the Discord message the squeak was posted as
+    pub fn discord_message(mut self, channel_id: String, message_id: String) -> Self {
+        self.discord_channel_id = Some(channel_id);
+        self.discord_message_id = Some(message_id);
+        self
+    }
+
+    /// Sets the name of the character who posted the squeak
+    pub fn character(mut self, name: String) -> Self {
+        self.character = Some(name);
+        self
+    }
+
+    /// Makes the squeak a reply to another one, in the conversation started by `thread_id`
+    pub fn reply_to(mut self, parent_id: ulid::Ulid, thread_id: ulid::Ulid) -> Self {
+        self.parent_id = Some(parent_id);
+        self.thread_id = Some(thread_id);
+        self
+    }
+
+    /// Makes the squeak a resqueak of another one, quoting it when the squeak has content
+    pub fn resqueak(mut self, original_id: ulid::Ulid) -> Self {
+        self.resqueak_of = Some(original_id);
+        self
+    }
+
     /// Builds the squeak
     pub fn build(self) -> Result<Squeak, SqueakError> {
         let Some(user_name) = self.user_name else {
@@ -59,20 +119,21 @@ impl SqueakBuilder {
                 name: user_name,
                 avatar_url,
             },
+            schema_version: SQUEAK_SCHEMA_VERSION,
+            created_at: Some(self.created_at.unwrap_or_else(OffsetDateTime::now_utc)),
+            edited_at: self.edited_at,
+            source: self.source,
+            discord_message_id: self.discord_message_id,
+            discord_channel_id: self.discord_channel_id,
+            character: self.character,
+            parent_id: self.parent_id,
+            thread_id: self.thread_id,
+            resqueak_of: self.resqueak_of,
+            likes: 0,
         })
     }
 }
 
-impl Default for SqueakBuilder {
-    fn default() -> Self {
-        SqueakBuilder {
-            content: None,
-            user_name: None,
-            avatar_url: None,
-        }
-    }
-}
-
 impl IntoFuture for SqueakBuilder {
     type Output = Result<Squeak, SqueakError>;
     type IntoFuture = futures::future::Ready<Self::Output>;
@@ -82,11 +143,76 @@ impl IntoFuture for SqueakBuilder {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Where a squeak was posted from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SqueakSource {
+    /// Squeaks from before sources were recorded all came from Discord
+    #[default]
+    Discord,
+    Game,
+    Web,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct Squeak {
     pub id: ulid::Ulid,
     pub content: String,
     pub author: User,
+    /// Version of the squeak schema, 1 for squeaks from before it was recorded
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    /// When the squeak was created, missing on squeaks from before it was recorded
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<OffsetDateTime>,
+    /// When the squeak was last edited
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub edited_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub source: SqueakSource,
+    /// ID of the Discord message the squeak was posted as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discord_message_id: Option<String>,
+    /// ID of the Discord channel the squeak was posted in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discord_channel_id: Option<String>,
+    /// Name of the character who posted the squeak
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub character: Option<String>,
+    /// The squeak this one replies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<ulid::Ulid>,
+    /// The squeak that started the conversation this one replies in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<ulid::Ulid>,
+    /// The squeak this one re-shares, its content is the quote and is empty for a plain resqueak
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resqueak_of: Option<ulid::Ulid>,
+    /// How many characters like the squeak, filled in by oblivion-server as it serves it
+    #[serde(default, deserialize_with = "ignore_likes")]
+    #[schema(read_only)]
+    pub likes: u64,
+}
+
+/// Reads past a squeak's like count, so a published squeak can't claim likes it doesn't have
+fn ignore_likes<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    serde::de::IgnoredAny::deserialize(deserializer)?;
+    Ok(0)
+}
+
+/// Squeaks stored before the schema version was recorded
+fn legacy_schema_version() -> u32 {
+    1
 }
 
 impl Squeak {
@@ -94,15 +220,72 @@ impl Squeak {
         SqueakBuilder::default()
     }
 
+    /// Applies an edit to the squeak
+    pub fn apply_edit(&mut self, edit: &SqueakEdit) {
+        self.content = edit.content.clone();
+        self.edited_at = Some(edit.edited_at);
+    }
+
+    /// Checks whether a character posted the squeak, going by the author for squeaks from before
+    /// the character was recorded
+    pub fn is_by(&self, character: &str) -> bool {
+        self.character
+            .as_deref()
+            .unwrap_or(&self.author.name)
+            .eq_ignore_ascii_case(character)
+    }
+
+    /// The squeak that started the conversation, which is this one unless it's a reply
+    pub fn thread_root(&self) -> ulid::Ulid {
+        self.thread_id.unwrap_or(self.id)
+    }
+
+    /// When the squeak was posted, falling back to its ULID for squeaks without a creation time,
+    /// and to the Unix epoch if the ULID's time can't be represented
+    pub fn posted_at(&self) -> OffsetDateTime {
+        self.created_at
+            .or_else(|| ulid_created_at(self.id))
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+    }
+
     pub fn get_subject() -> String {
+        format!("{}.post", Self::get_subject_root())
+    }
+
+    /// Gets the subject filter matching every skeever event
+    pub fn get_subject_filter() -> String {
+        format!("{}.>", Self::get_subject_root())
+    }
+
+    pub(crate) fn get_subject_root() -> String {
         let prefix =
             OddbotConfig::get_event_stream_prefix().unwrap_or("oddlaws.events".to_string());
-        format!("{}.skeever.post", prefix)
+        format!("{}.skeever", prefix)
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct User {
     pub name: String,
     pub avatar_url: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_counts_are_written_but_never_read() {
+        let mut squeak: Squeak = serde_json::from_value(serde_json::json!({
+            "id": ulid::Ulid::new(),
+            "content": "Ulfric lies",
+            "author": { "name": "Ulfric", "avatar_url": "" },
+            "likes": 9000,
+        }))
+        .unwrap();
+        assert_eq!(squeak.likes, 0);
+
+        squeak.likes = 3;
+        assert_eq!(serde_json::to_value(&squeak).unwrap()["likes"], 3);
+    }
+}